@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Decides whether the window of a `MarkerDetector` is a marker. It is told about every
+/// symbol entering and leaving the window, so it can keep its own bookkeeping instead of
+/// looking at the whole window again.
+pub trait MarkerCondition<T> {
+    fn enter(&mut self, symbol: T);
+    fn leave(&mut self, symbol: T);
+    fn clear(&mut self);
+    /// Only asked once the window holds the full number of symbols.
+    fn is_marker(&self, window: &VecDeque<T>) -> bool;
+}
+
+// how often each symbol occurs in the window
+struct Counts<T>(HashMap<T, usize>);
+
+impl<T: Hash + Eq + Copy> Counts<T> {
+    fn new() -> Self {
+        Counts(HashMap::new())
+    }
+
+    // returns the new count of the symbol
+    fn add(&mut self, symbol: T) -> usize {
+        let count = self.0.entry(symbol).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn remove(&mut self, symbol: T) -> usize {
+        let count = self.0.get_mut(&symbol).expect("removed symbol is counted");
+        *count -= 1;
+        let count = *count;
+        if count == 0 {
+            self.0.remove(&symbol);
+        }
+        count
+    }
+}
+
+/// No symbol occurs more than `max` times.
+pub struct AtMostRepeats<T> {
+    max: usize,
+    counts: Counts<T>,
+    // how many symbols occur more than `max` times
+    exceeding: usize,
+}
+
+impl<T: Hash + Eq + Copy> AtMostRepeats<T> {
+    pub fn new(max: usize) -> Self {
+        AtMostRepeats {
+            max,
+            counts: Counts::new(),
+            exceeding: 0,
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> MarkerCondition<T> for AtMostRepeats<T> {
+    fn enter(&mut self, symbol: T) {
+        if self.counts.add(symbol) == self.max + 1 {
+            self.exceeding += 1;
+        }
+    }
+
+    fn leave(&mut self, symbol: T) {
+        if self.counts.remove(symbol) == self.max {
+            self.exceeding -= 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.counts.0.clear();
+        self.exceeding = 0;
+    }
+
+    fn is_marker(&self, _window: &VecDeque<T>) -> bool {
+        self.exceeding == 0
+    }
+}
+
+/// All symbols are different, the condition of the puzzle.
+pub struct AllDistinct<T>(AtMostRepeats<T>);
+
+impl<T: Hash + Eq + Copy> AllDistinct<T> {
+    pub fn new() -> Self {
+        AllDistinct(AtMostRepeats::new(1))
+    }
+}
+
+impl<T: Hash + Eq + Copy> Default for AllDistinct<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Hash + Eq + Copy> MarkerCondition<T> for AllDistinct<T> {
+    fn enter(&mut self, symbol: T) {
+        self.0.enter(symbol)
+    }
+
+    fn leave(&mut self, symbol: T) {
+        self.0.leave(symbol)
+    }
+
+    fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    fn is_marker(&self, window: &VecDeque<T>) -> bool {
+        self.0.is_marker(window)
+    }
+}
+
+/// Every symbol of the alphabet occurs at least once.
+pub struct ContainsAll<T> {
+    alphabet: HashSet<T>,
+    counts: Counts<T>,
+    // how many symbols of the alphabet are in the window
+    present: usize,
+}
+
+impl<T: Hash + Eq + Copy> ContainsAll<T> {
+    pub fn new<A: IntoIterator<Item = T>>(alphabet: A) -> Self {
+        ContainsAll {
+            alphabet: alphabet.into_iter().collect(),
+            counts: Counts::new(),
+            present: 0,
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> MarkerCondition<T> for ContainsAll<T> {
+    fn enter(&mut self, symbol: T) {
+        if self.alphabet.contains(&symbol) && self.counts.add(symbol) == 1 {
+            self.present += 1;
+        }
+    }
+
+    fn leave(&mut self, symbol: T) {
+        if self.alphabet.contains(&symbol) && self.counts.remove(symbol) == 0 {
+            self.present -= 1;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.counts.0.clear();
+        self.present = 0;
+    }
+
+    fn is_marker(&self, _window: &VecDeque<T>) -> bool {
+        self.present == self.alphabet.len()
+    }
+}
+
+/// Any check on the window. It looks at the whole window for every symbol.
+pub struct Custom<F>(pub F);
+
+impl<T, F: Fn(&VecDeque<T>) -> bool> MarkerCondition<T> for Custom<F> {
+    fn enter(&mut self, _symbol: T) {}
+
+    fn leave(&mut self, _symbol: T) {}
+
+    fn clear(&mut self) {}
+
+    fn is_marker(&self, window: &VecDeque<T>) -> bool {
+        (self.0)(window)
+    }
+}
@@ -1,5 +1,12 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::io::{ErrorKind, Read};
+
 use anyhow::Error;
-use iter_tools::Itertools;
+
+use crate::day06::condition::{AllDistinct, MarkerCondition};
+
+pub mod condition;
 
 pub fn part1(input: Vec<String>) -> Result<String, Error> {
     Ok(index_of_marker(&input.join(""), 4)
@@ -12,20 +19,258 @@ pub fn part2(input: Vec<String>) -> Result<String, Error> {
         .unwrap_or("No message found".to_string()))
 }
 
+/// Slides a window over the symbols it is fed and lets the condition keep track of the
+/// symbols in it, so every symbol is looked at only once.
+pub struct MarkerDetector<T, C = AllDistinct<T>> {
+    length: usize,
+    position: usize,
+    window: VecDeque<T>,
+    condition: C,
+}
+
+impl<T: Hash + Eq + Copy> MarkerDetector<T> {
+    pub fn new(length: usize) -> Self {
+        Self::with_condition(length, AllDistinct::new())
+    }
+}
+
+impl<T: Copy, C: MarkerCondition<T>> MarkerDetector<T, C> {
+    pub fn with_condition(length: usize, condition: C) -> Self {
+        MarkerDetector {
+            length,
+            position: 0,
+            window: VecDeque::with_capacity(length + 1),
+            condition,
+        }
+    }
+
+    /// Adds the next symbol. Returns the number of symbols seen so far if the last `length`
+    /// of them are a marker.
+    pub fn push(&mut self, symbol: T) -> Option<usize> {
+        self.position += 1;
+        self.window.push_back(symbol);
+        self.condition.enter(symbol);
+        if self.window.len() > self.length {
+            let dropped = self.window.pop_front().expect("window is not empty");
+            self.condition.leave(dropped);
+        }
+        if self.window.len() == self.length && self.condition.is_marker(&self.window) {
+            Some(self.position)
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the symbols in the window, but keeps counting positions.
+    pub fn clear(&mut self) {
+        self.window.clear();
+        self.condition.clear();
+    }
+}
+
+/// Reads a signal in chunks and yields the position of every marker as soon as the chunk
+/// containing it has been read. After a marker the search starts over with the next symbol,
+/// so markers never overlap. Line breaks are not part of the signal.
+pub struct MarkerStream<R> {
+    reader: R,
+    detector: MarkerDetector<u8>,
+    buffer: Vec<u8>,
+    filled: usize,
+    consumed: usize,
+    done: bool,
+}
+
+impl<R: Read> MarkerStream<R> {
+    pub fn new(reader: R, length: usize) -> Self {
+        Self::with_chunk_size(reader, length, 64 * 1024)
+    }
+
+    pub fn with_chunk_size(reader: R, length: usize, chunk_size: usize) -> Self {
+        MarkerStream {
+            reader,
+            detector: MarkerDetector::new(length),
+            buffer: vec![0; chunk_size.max(1)],
+            filled: 0,
+            consumed: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for MarkerStream<R> {
+    type Item = std::io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            while self.consumed < self.filled {
+                let symbol = self.buffer[self.consumed];
+                self.consumed += 1;
+                if symbol == b'\n' || symbol == b'\r' {
+                    continue;
+                }
+                if let Some(position) = self.detector.push(symbol) {
+                    self.detector.clear();
+                    return Some(Ok(position));
+                }
+            }
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => self.done = true,
+                Ok(read) => {
+                    self.filled = read;
+                    self.consumed = 0;
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The position right after the first marker in the stream.
+pub fn first_marker_in<R: Read>(reader: R, length: usize) -> std::io::Result<Option<usize>> {
+    MarkerStream::new(reader, length).next().transpose()
+}
+
+/// The position right after the first `length` distinct symbols.
+pub fn find_marker<T: Hash + Eq + Copy, A: IntoIterator<Item = T>>(
+    symbols: A,
+    length: usize,
+) -> Option<usize> {
+    find_marker_with(symbols, length, AllDistinct::new())
+}
+
+/// The position right after the first `length` symbols that fulfill the condition.
+pub fn find_marker_with<T: Copy, C: MarkerCondition<T>, A: IntoIterator<Item = T>>(
+    symbols: A,
+    length: usize,
+    condition: C,
+) -> Option<usize> {
+    let mut detector = MarkerDetector::with_condition(length, condition);
+    symbols.into_iter().find_map(|symbol| detector.push(symbol))
+}
+
+// counts chars, not bytes
 fn index_of_marker(text: &str, length: usize) -> Option<usize> {
-    for idx in 0..text.len() {
-        if idx + length > text.len() {
-            return None;
-        } else if text[idx..(idx + length)].chars().sorted().dedup().count() == length {
-            return Some(idx + length);
+    find_marker(text.chars(), length)
+}
+
+pub fn index_of_marker_in_bytes(bytes: &[u8], length: usize) -> Option<usize> {
+    find_marker(bytes.iter().copied(), length)
+}
+
+/// Every position right after `length` distinct symbols, markers may overlap.
+pub fn find_markers<T: Hash + Eq + Copy, A: IntoIterator<Item = T>>(
+    symbols: A,
+    length: usize,
+) -> impl Iterator<Item = usize> {
+    find_markers_with(symbols, length, AllDistinct::new())
+}
+
+pub fn find_markers_with<T: Copy, C: MarkerCondition<T>, A: IntoIterator<Item = T>>(
+    symbols: A,
+    length: usize,
+    condition: C,
+) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::with_condition(length, condition);
+    symbols
+        .into_iter()
+        .filter_map(move |symbol| detector.push(symbol))
+}
+
+/// Like `find_markers`, but like `MarkerStream` the search starts over after each marker,
+/// so markers never overlap.
+pub fn find_separate_markers<T: Hash + Eq + Copy, A: IntoIterator<Item = T>>(
+    symbols: A,
+    length: usize,
+) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::new(length);
+    symbols.into_iter().filter_map(move |symbol| {
+        let position = detector.push(symbol);
+        if position.is_some() {
+            detector.clear();
+        }
+        position
+    })
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MarkerKind {
+    StartOfPacket,
+    StartOfMessage,
+}
+
+impl MarkerKind {
+    pub fn length(&self) -> usize {
+        match self {
+            MarkerKind::StartOfPacket => 4,
+            MarkerKind::StartOfMessage => 14,
         }
     }
-    None
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub position: usize,
+}
+
+/// All start-of-packet and start-of-message markers in the order they appear in the signal,
+/// including overlapping ones.
+pub fn markers(text: &str) -> impl Iterator<Item = Marker> + '_ {
+    let mut packets = MarkerDetector::new(MarkerKind::StartOfPacket.length());
+    let mut messages = MarkerDetector::new(MarkerKind::StartOfMessage.length());
+    text.chars().flat_map(move |symbol| {
+        let mut found = vec![];
+        for (kind, detector) in [
+            (MarkerKind::StartOfPacket, &mut packets),
+            (MarkerKind::StartOfMessage, &mut messages),
+        ] {
+            if let Some(position) = detector.push(symbol) {
+                found.push(Marker { kind, position });
+            }
+        }
+        found
+    })
+}
+
+/// Splits the signal at the markers of the given kind. Every body starts right after its
+/// marker and ends where the window of the next marker begins.
+pub fn bodies(text: &str, kind: MarkerKind) -> Vec<&str> {
+    // byte offset of every char, and of the end of the text
+    let offsets: Vec<usize> = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([text.len()])
+        .collect();
+    let positions: Vec<usize> = find_separate_markers(text.chars(), kind.length()).collect();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(idx, start)| {
+            let end = positions
+                .get(idx + 1)
+                .map(|next| next - kind.length())
+                .unwrap_or(offsets.len() - 1);
+            &text[offsets[*start]..offsets[end]]
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day06::index_of_marker;
+    use std::collections::VecDeque;
+    use std::io::{Cursor, Read};
+
+    use crate::day06::condition::{AtMostRepeats, ContainsAll, Custom};
+    use crate::day06::{
+        bodies, find_marker_with, find_markers, find_markers_with, find_separate_markers,
+        first_marker_in, index_of_marker, index_of_marker_in_bytes, markers, Marker, MarkerKind,
+        MarkerStream,
+    };
 
     #[test]
     pub fn test_examples_4() {
@@ -43,4 +288,155 @@ mod tests {
             Some(23)
         );
     }
+    #[test]
+    pub fn test_bytes() {
+        assert_eq!(
+            index_of_marker_in_bytes(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4),
+            Some(7)
+        );
+        assert_eq!(
+            index_of_marker_in_bytes(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14),
+            Some(19)
+        );
+        assert_eq!(index_of_marker_in_bytes(b"aaaaaaa", 4), None);
+    }
+    #[test]
+    pub fn test_multi_byte_chars() {
+        assert_eq!(index_of_marker("ääöüßxyz", 4), Some(5));
+        assert_eq!(index_of_marker("🎄🎄🎅🎁", 3), Some(4));
+        assert_eq!(index_of_marker("äää", 2), None);
+    }
+
+    // hands out one byte per read, like a slow pipe
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((first, rest)) if !buf.is_empty() => {
+                    buf[0] = *first;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    // fails as soon as it is read from
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("pipe closed"))
+        }
+    }
+
+    #[test]
+    pub fn test_stream() {
+        let signal = b"mjqjpqmgbl\njsphdztnvjfqwrcgsmlb";
+        assert_eq!(first_marker_in(Trickle(signal), 4).unwrap(), Some(7));
+        assert_eq!(first_marker_in(Trickle(signal), 14).unwrap(), Some(19));
+        assert_eq!(
+            MarkerStream::with_chunk_size(&signal[..], 4, 3)
+                .collect::<std::io::Result<Vec<usize>>>()
+                .unwrap(),
+            vec![7, 11, 15, 19, 23, 27]
+        );
+    }
+
+    #[test]
+    pub fn test_stream_reports_markers_before_reading_on() {
+        let mut markers = MarkerStream::new(Cursor::new(b"aabcd").chain(Broken), 4);
+        assert_eq!(markers.next().unwrap().unwrap(), 5);
+        assert!(markers.next().unwrap().is_err());
+        assert!(markers.next().is_none());
+    }
+
+    #[test]
+    pub fn test_long_stream() {
+        let noise = std::io::repeat(b'a').take(1_000_000);
+        let signal = noise.chain(Cursor::new(b"abcd"));
+        assert_eq!(first_marker_in(signal, 4).unwrap(), Some(1_000_004));
+    }
+
+    #[test]
+    pub fn test_all_markers() {
+        let packet = |position| Marker {
+            kind: MarkerKind::StartOfPacket,
+            position,
+        };
+        let message = |position| Marker {
+            kind: MarkerKind::StartOfMessage,
+            position,
+        };
+        // every window from pqmg on is a packet marker
+        let expected: Vec<Marker> = (7..=30)
+            .flat_map(|position| {
+                let message = (position == 19 || position >= 25).then(|| message(position));
+                std::iter::once(packet(position)).chain(message)
+            })
+            .collect();
+        assert_eq!(
+            markers("mjqjpqmgbljsphdztnvjfqwrcgsmlb").collect::<Vec<Marker>>(),
+            expected
+        );
+        assert_eq!(
+            find_markers("abcabcxx".chars(), 3).collect::<Vec<_>>(),
+            vec![3, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            find_separate_markers("abcabcxx".chars(), 3).collect::<Vec<_>>(),
+            vec![3, 6]
+        );
+    }
+
+    #[test]
+    pub fn test_bodies() {
+        assert_eq!(
+            bodies("abcdzzzzefghzz", MarkerKind::StartOfPacket),
+            vec!["zzz", "hzz"]
+        );
+        assert_eq!(
+            bodies("äöüßßßßxyzw", MarkerKind::StartOfPacket),
+            vec!["ßß", "w"]
+        );
+        assert!(bodies("aaaa", MarkerKind::StartOfMessage).is_empty());
+    }
+
+    #[test]
+    pub fn test_at_most_repeats() {
+        let signal = "mjqjpqmgbljsphdztnvjfqwrcgsmlb".chars();
+        assert_eq!(
+            find_marker_with(signal.clone(), 4, AtMostRepeats::new(1)),
+            Some(7)
+        );
+        assert_eq!(find_marker_with(signal, 4, AtMostRepeats::new(2)), Some(4));
+        assert_eq!(
+            find_marker_with("aaabbbcc".chars(), 4, AtMostRepeats::new(2)),
+            Some(5)
+        );
+    }
+
+    #[test]
+    pub fn test_contains_all() {
+        let signal = "xxaxxbxcxab".chars();
+        assert_eq!(
+            find_marker_with(signal.clone(), 6, ContainsAll::new(['a', 'b', 'c'])),
+            Some(8)
+        );
+        assert_eq!(
+            find_markers_with(signal, 3, ContainsAll::new(['a', 'b', 'c'])).collect::<Vec<_>>(),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    pub fn test_custom_condition() {
+        let palindrome = Custom(|window: &VecDeque<u8>| window.iter().eq(window.iter().rev()));
+        assert_eq!(
+            find_marker_with(b"abcdcbe".iter().copied(), 5, palindrome),
+            Some(6)
+        );
+    }
 }
@@ -0,0 +1,184 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+use crate::day07::{join_path, Entry, FileTree};
+
+/// How a file or directory differs, with its sizes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Change {
+    Added(u64),
+    Removed(u64),
+    Resized { before: u64, after: u64 },
+}
+
+/// The differences between two trees, everything sorted by path apart from the deltas.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TreeDiff {
+    pub files: Vec<(String, Change)>,
+    /// Directories that were added or removed.
+    pub dirs: Vec<(String, Change)>,
+    /// How much every directory grew or shrank, biggest growth first.
+    pub deltas: Vec<(String, i64)>,
+}
+
+impl TreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.dirs.is_empty()
+    }
+}
+
+impl Display for TreeDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // directories end with a slash
+        let dirs = self
+            .dirs
+            .iter()
+            .map(|(dir, change)| (format!("{}/", dir), change));
+        let files = self
+            .files
+            .iter()
+            .map(|(file, change)| (file.clone(), change));
+        for (path, change) in dirs.chain(files) {
+            match change {
+                Change::Added(size) => writeln!(f, "+ {} ({})", path, size)?,
+                Change::Removed(size) => writeln!(f, "- {} ({})", path, size)?,
+                Change::Resized { before, after } => {
+                    writeln!(f, "~ {} ({} -> {})", path, before, after)?
+                }
+            }
+        }
+        for (dir, delta) in &self.deltas {
+            writeln!(f, "{:+}\t{}", delta, dir)?;
+        }
+        Ok(())
+    }
+}
+
+// every file in the tree with its size
+fn files<T: FileTree + ?Sized>(tree: &T) -> BTreeMap<String, u64> {
+    let mut files = BTreeMap::new();
+    let mut todo = vec!["/".to_string()];
+    while let Some(dir) = todo.pop() {
+        for entry in tree.ls(&dir) {
+            match entry {
+                Entry::Dir(name) => todo.push(join_path(&dir, &name)),
+                Entry::File(size, name) => {
+                    files.insert(join_path(&dir, &name), size);
+                }
+            }
+        }
+    }
+    files
+}
+
+fn changes(
+    before: &BTreeMap<String, u64>,
+    after: &BTreeMap<String, u64>,
+    resized: bool,
+) -> Vec<(String, Change)> {
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let change = match (before.get(path), after.get(path)) {
+                (None, Some(size)) => Change::Added(*size),
+                (Some(size), None) => Change::Removed(*size),
+                (Some(before), Some(after)) if resized && before != after => Change::Resized {
+                    before: *before,
+                    after: *after,
+                },
+                _ => return None,
+            };
+            Some((path.clone(), change))
+        })
+        .collect()
+}
+
+/// What changed from `before` to `after`, for example two transcripts taken at different times.
+pub fn diff<A: FileTree + ?Sized, B: FileTree + ?Sized>(before: &A, after: &B) -> TreeDiff {
+    let dirs_before: BTreeMap<String, u64> = before.dirs_sized().into_iter().collect();
+    let dirs_after: BTreeMap<String, u64> = after.dirs_sized().into_iter().collect();
+
+    let mut deltas: Vec<(String, i64)> = dirs_before
+        .keys()
+        .chain(dirs_after.keys())
+        .collect::<BTreeSet<&String>>()
+        .into_iter()
+        .map(|dir| {
+            let size = |dirs: &BTreeMap<String, u64>| *dirs.get(dir).unwrap_or(&0) as i64;
+            (dir.clone(), size(&dirs_after) - size(&dirs_before))
+        })
+        .filter(|(_, delta)| *delta != 0)
+        .collect();
+    deltas.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    TreeDiff {
+        files: changes(&files(before), &files(after), true),
+        dirs: changes(&dirs_before, &dirs_after, false),
+        deltas,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::fixtures::example;
+    use crate::day07::with_map::create_file_tree;
+
+    #[test]
+    pub fn same_trees_do_not_differ() {
+        let map_tree = create_file_tree(crate::day07::from_disk::transcript(&example()));
+        assert!(diff(&example(), &map_tree).is_empty());
+        assert_eq!("", diff(&example(), &example()).to_string());
+    }
+
+    #[test]
+    pub fn shows_where_the_disk_grew() {
+        let before = example();
+        let mut after = example();
+        after.rm("/a/e").unwrap();
+        after.touch("/a/g", 3000).unwrap();
+        after.mkdir("/logs").unwrap();
+        after.touch("/logs/today", 1000).unwrap();
+        after.rm("/c.dat").unwrap();
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            vec![
+                ("/a/e/i".to_string(), Change::Removed(584)),
+                (
+                    "/a/g".to_string(),
+                    Change::Resized {
+                        before: 2557,
+                        after: 3000
+                    }
+                ),
+                ("/c.dat".to_string(), Change::Removed(8504156)),
+                ("/logs/today".to_string(), Change::Added(1000)),
+            ],
+            diff.files
+        );
+        assert_eq!(
+            vec![
+                ("/a/e".to_string(), Change::Removed(584)),
+                ("/logs".to_string(), Change::Added(1000)),
+            ],
+            diff.dirs
+        );
+        assert_eq!(
+            vec![
+                ("/logs".to_string(), 1000),
+                ("/a".to_string(), -141),
+                ("/a/e".to_string(), -584),
+                ("/".to_string(), -8503297),
+            ],
+            diff.deltas
+        );
+        assert!(diff
+            .to_string()
+            .starts_with("- /a/e/ (584)\n+ /logs/ (1000)\n- /a/e/i (584)\n"));
+        assert!(diff
+            .to_string()
+            .ends_with("+1000\t/logs\n-141\t/a\n-584\t/a/e\n-8503297\t/\n"));
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Error};
+
+use crate::day07::query::DirQuery;
+use crate::day07::FileTree;
+
+// the plan keeps four bytes per total it can delete, and a bit per total for every
+// nesting level it is inside of. Above this many totals sizes are counted in
+// coarser units and the plan may not be the best
+const MAX_TOTALS: usize = 1 << 24;
+// every directory touches every word of the bitset of totals once, with more
+// directories the totals are counted in coarser units as well
+const MAX_WORK: usize = 1 << 26;
+
+/// The size of the disk and how much of it has to be free for the update.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Disk {
+    pub capacity: u64,
+    pub required_free: u64,
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Disk {
+            capacity: 70000000,
+            required_free: 30000000,
+        }
+    }
+}
+
+/// Directories that free enough space when deleted together, none of them inside another.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CleanupPlan {
+    pub dirs: Vec<(String, u64)>,
+    pub freed: u64,
+}
+
+impl Disk {
+    pub fn new(capacity: u64, required_free: u64) -> Self {
+        Disk {
+            capacity,
+            required_free,
+        }
+    }
+
+    /// How much has to be deleted, given how much of the disk is used.
+    pub fn space_to_free(&self, used: u64) -> Result<u64, Error> {
+        if self.required_free > self.capacity {
+            return Err(anyhow!(
+                "{} can never be free on a disk of {}",
+                self.required_free,
+                self.capacity
+            ));
+        }
+        let available = self.capacity.checked_sub(used).ok_or_else(|| {
+            anyhow!(
+                "The files take {}, more than the capacity of {}",
+                used,
+                self.capacity
+            )
+        })?;
+        Ok(self.required_free.saturating_sub(available))
+    }
+
+    /// The smallest single directory that frees enough space.
+    pub fn smallest_dir_to_delete<T: FileTree + ?Sized>(
+        &self,
+        tree: &T,
+    ) -> Result<Option<(String, u64)>, Error> {
+        let needed = self.space_to_free(tree.dir_size("/"))?;
+        Ok(tree
+            .query(&DirQuery::new().min_size(needed))
+            .into_iter()
+            .min_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0))))
+    }
+
+    /// The directories to delete so that the least is deleted in total. This can be several
+    /// smaller directories instead of the smallest one that is big enough on its own.
+    /// Memory grows with the size of the smallest directory that is big enough, up to
+    /// about 64MB plus 2MB per nesting level, and time with that size times the number
+    /// of directories. Beyond that sizes are rounded to coarser units, the plan then
+    /// still frees enough and never deletes more than that directory, but may not be
+    /// the best.
+    pub fn cleanup_plan<T: FileTree + ?Sized>(&self, tree: &T) -> Result<CleanupPlan, Error> {
+        let needed = self.space_to_free(tree.dir_size("/"))?;
+        if needed == 0 {
+            return Ok(CleanupPlan {
+                dirs: vec![],
+                freed: 0,
+            });
+        }
+        let single = self
+            .smallest_dir_to_delete(tree)?
+            .ok_or_else(|| anyhow!("Deleting everything does not free {}", needed))?;
+        // depth first order, so everything inside a directory follows it
+        let mut dirs = tree.dirs_sized();
+        dirs.sort_by_cached_key(|(dir, _size)| {
+            dir.split('/')
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        });
+        let count = u32::try_from(dirs.len()).map_err(|_| anyhow!("Too many directories"))?;
+        // only totals below the single directory improve the plan
+        let max_totals = MAX_TOTALS.min(MAX_WORK / dirs.len() * 64);
+        let unit = (single.1 as usize).div_ceil(max_totals).max(1);
+        let limit = (single.1 as usize).div_ceil(unit);
+        // the index after everything inside each directory
+        let mut ends = vec![dirs.len(); dirs.len()];
+        let mut open: Vec<usize> = vec![];
+        for (idx, (dir, _size)) in dirs.iter().enumerate() {
+            while let Some(&parent) = open.last() {
+                if contains(&dirs[parent].0, dir) {
+                    break;
+                }
+                ends[parent] = idx;
+                open.pop();
+            }
+            open.push(idx);
+        }
+
+        // subset sum from the back, with a bitset of reachable totals. `first[total]` is the
+        // first directory of a plan deleting `total`, the rest of that plan comes after the
+        // directories inside it.
+        let mut first = vec![u32::MAX; limit];
+        first[0] = count;
+        let mut reachable = vec![0u64; limit.div_ceil(64)];
+        reachable[0] = 1;
+        // the totals reachable from an index on, kept while a directory ending there is to come
+        let mut waiting: HashMap<usize, usize> = HashMap::new();
+        for (idx, end) in ends.iter().enumerate() {
+            if *end != idx + 1 {
+                *waiting.entry(*end).or_default() += 1;
+            }
+        }
+        let mut saved: HashMap<usize, Vec<u64>> = HashMap::new();
+        for idx in (0..dirs.len()).rev() {
+            if waiting.contains_key(&(idx + 1)) {
+                saved.insert(idx + 1, reachable.clone());
+            }
+            let size = dirs[idx].1 as usize / unit;
+            let mut add = |base: Option<&[u64]>| {
+                if size > 0 && size < limit {
+                    add_shifted(base, size, &mut reachable, limit, |total| {
+                        first[total] = idx as u32
+                    });
+                }
+            };
+            if ends[idx] == idx + 1 {
+                // nothing inside, the totals from here on are the ones in `reachable`
+                add(None);
+                continue;
+            }
+            add(Some(&saved[&ends[idx]]));
+            let count = waiting.get_mut(&ends[idx]).expect("counted above");
+            *count -= 1;
+            if *count == 0 {
+                waiting.remove(&ends[idx]);
+                saved.remove(&ends[idx]);
+            }
+        }
+
+        // what the plan deleting `total` frees, in bytes
+        let freed_by = |mut total: usize| {
+            let mut freed = 0;
+            while total > 0 {
+                let size = dirs[first[total] as usize].1;
+                freed += size;
+                total -= size as usize / unit;
+            }
+            freed
+        };
+        // sizes are rounded down, so totals a little below what is needed may be enough
+        let needed_units = (needed as usize).div_ceil(unit);
+        let best = (needed_units.saturating_sub(64)..limit)
+            .filter(|total| first[*total] != u32::MAX)
+            .find(|total| freed_by(*total) >= needed)
+            // with coarser units the plan may delete more than the single directory
+            .filter(|total| freed_by(*total) < single.1);
+        let mut plan = match best {
+            None => vec![single],
+            Some(mut total) => {
+                let mut plan = vec![];
+                while total > 0 {
+                    let (dir, size) = &dirs[first[total] as usize];
+                    plan.push((dir.clone(), *size));
+                    total -= *size as usize / unit;
+                }
+                plan
+            }
+        };
+        let freed = plan.iter().map(|(_dir, size)| size).sum();
+        plan.sort();
+        Ok(CleanupPlan { dirs: plan, freed })
+    }
+}
+
+// ors `base` shifted up by `shift` bits into `bits` below `limit`, reporting every new bit.
+// Without a base `bits` is shifted into itself, from the top so every word is read first.
+fn add_shifted(
+    base: Option<&[u64]>,
+    shift: usize,
+    bits: &mut [u64],
+    limit: usize,
+    mut added: impl FnMut(usize),
+) {
+    let (words, offset) = (shift / 64, shift % 64);
+    for idx in (words..bits.len()).rev() {
+        let base = base.unwrap_or(bits);
+        let mut word = base[idx - words] << offset;
+        if offset > 0 && idx > words {
+            word |= base[idx - words - 1] >> (64 - offset);
+        }
+        if (idx + 1) * 64 > limit {
+            word &= (1 << (limit - idx * 64)) - 1;
+        }
+        let mut new = word & !bits[idx];
+        bits[idx] |= new;
+        while new != 0 {
+            added(idx * 64 + new.trailing_zeros() as usize);
+            new &= new - 1;
+        }
+    }
+}
+
+// whether one of the directories is inside the other
+fn contains(a: &str, b: &str) -> bool {
+    let inside = |outer: &str, inner: &str| {
+        outer == "/"
+            || inner
+                .strip_prefix(outer)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+    inside(a, b) || inside(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::with_nodes::NodeTree;
+    use crate::day07::Entry;
+
+    // a flat tree of directories below the root, each with one file
+    struct Flat(Vec<(String, u64)>);
+
+    impl FileTree for Flat {
+        fn dir_size(&self, dir: &str) -> u64 {
+            if dir == "/" {
+                self.0.iter().map(|(_, size)| size).sum()
+            } else {
+                self.0
+                    .iter()
+                    .find(|(name, _)| *name == dir)
+                    .map_or(0, |(_, size)| *size)
+            }
+        }
+
+        fn dirs(&self) -> Vec<String> {
+            let mut dirs = vec!["/".to_string()];
+            dirs.extend(self.0.iter().map(|(name, _)| name.clone()));
+            dirs
+        }
+
+        fn ls(&self, _dir: &str) -> Vec<Entry> {
+            vec![]
+        }
+    }
+
+    #[test]
+    pub fn space_to_free_is_checked() {
+        let disk = Disk::new(100, 30);
+        assert_eq!(0, disk.space_to_free(50).unwrap());
+        assert_eq!(10, disk.space_to_free(80).unwrap());
+        assert_eq!(30, disk.space_to_free(100).unwrap());
+        assert!(disk.space_to_free(101).is_err());
+        assert!(Disk::new(10, 20).space_to_free(0).is_err());
+    }
+
+    #[test]
+    pub fn plan_combines_small_dirs() {
+        let tree = Flat(vec![
+            ("/a".to_string(), 40),
+            ("/b".to_string(), 25),
+            ("/c".to_string(), 20),
+            ("/d".to_string(), 10),
+        ]);
+        let disk = Disk::new(100, 40);
+        assert_eq!(
+            Some(("/a".to_string(), 40)),
+            disk.smallest_dir_to_delete(&tree).unwrap()
+        );
+        assert_eq!(
+            CleanupPlan {
+                dirs: vec![("/b".to_string(), 25), ("/d".to_string(), 10)],
+                freed: 35
+            },
+            disk.cleanup_plan(&tree).unwrap()
+        );
+        assert_eq!(
+            CleanupPlan {
+                dirs: vec![],
+                freed: 0
+            },
+            Disk::new(1000, 45).cleanup_plan(&tree).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn plans_for_big_disks_use_coarser_units() {
+        let gb = 1 << 30;
+        let tree = Flat(vec![
+            ("/a".to_string(), 40 * gb),
+            ("/b".to_string(), 25 * gb),
+            ("/c".to_string(), 20 * gb),
+            ("/d".to_string(), 10 * gb + 1),
+        ]);
+        let plan = Disk::new(100 * gb, 40 * gb).cleanup_plan(&tree).unwrap();
+        assert_eq!(
+            vec![("/b".to_string(), 25 * gb), ("/d".to_string(), 10 * gb + 1)],
+            plan.dirs
+        );
+        assert_eq!(35 * gb + 1, plan.freed);
+    }
+
+    #[test]
+    pub fn plans_for_many_dirs_stay_fast() {
+        // one big directory and 10000 small ones, about 26MB in total
+        let mut tree = NodeTree::new();
+        let big = tree.add_dir(0, "big").unwrap();
+        tree.add_file(big, "f", 16_000_000).unwrap();
+        for idx in 0..10000 {
+            let dir = tree.add_dir(0, &format!("d{}", idx)).unwrap();
+            tree.add_file(dir, "f", 1000 + idx % 97).unwrap();
+        }
+        tree.compute_sizes();
+        let disk = Disk::new(30_000_000, 12_000_000);
+        let needed = disk.space_to_free(tree.dir_size("/")).unwrap();
+        let plan = disk.cleanup_plan(&tree).unwrap();
+        assert!(plan.freed >= needed && plan.freed < 16_000_000);
+        assert!(plan.dirs.iter().all(|(dir, _)| dir != "/big" && dir != "/"));
+        assert_eq!(
+            plan.freed,
+            plan.dirs.iter().map(|(_, size)| size).sum::<u64>()
+        );
+    }
+
+    #[test]
+    pub fn nested_dirs_are_not_counted_twice() {
+        assert!(contains("/", "/a"));
+        assert!(contains("/a/b", "/a"));
+        assert!(!contains("/a", "/ab"));
+        assert!(!contains("/a/b", "/a/c"));
+    }
+}
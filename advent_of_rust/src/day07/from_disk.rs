@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+
+use crate::day07::with_nodes::{NodeId, NodeTree};
+use crate::day07::{join_path, Entry, Expr, FileTree};
+
+/// Reads a directory from disk into a `NodeTree`, with the directory as the root.
+/// Symbolic links are skipped so cycles can not happen.
+pub fn read_dir_tree<P: AsRef<Path>>(root: P) -> Result<NodeTree, Error> {
+    let mut tree = NodeTree::new();
+    let mut todo: Vec<(PathBuf, NodeId)> = vec![(root.as_ref().to_path_buf(), 0)];
+    while let Some((dir, id)) = todo.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|err| anyhow!("Can not read {}: {}", dir.display(), err))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let meta = entry.path().symlink_metadata()?;
+            if meta.is_dir() {
+                let child = tree.add_dir(id, &name)?;
+                todo.push((entry.path(), child));
+            } else if meta.is_file() {
+                tree.add_file(id, &name, meta.len())?;
+            }
+        }
+    }
+    tree.compute_sizes();
+    Ok(tree)
+}
+
+// a step of the transcript still to write
+enum Step {
+    Enter(String, String),
+    Leave,
+}
+
+/// The `$ cd` / `$ ls` session that explores the whole tree, depth first.
+/// Names with whitespace can not be parsed back.
+pub fn transcript<T: FileTree + ?Sized>(tree: &T) -> Vec<Expr> {
+    let mut exprs = vec![];
+    let mut todo = vec![Step::Enter("/".to_string(), "/".to_string())];
+    while let Some(step) = todo.pop() {
+        match step {
+            Step::Leave => exprs.push(Expr::Cd("..".to_string())),
+            Step::Enter(path, name) => {
+                exprs.push(Expr::Cd(name));
+                exprs.push(Expr::Ls);
+                let entries = tree.ls(&path);
+                let mut dirs = vec![];
+                for entry in entries {
+                    match entry {
+                        Entry::Dir(name) => {
+                            dirs.push(name.clone());
+                            exprs.push(Expr::Dir(name));
+                        }
+                        Entry::File(size, name) => exprs.push(Expr::File(size, name)),
+                    }
+                }
+                for name in dirs.into_iter().rev() {
+                    todo.push(Step::Leave);
+                    todo.push(Step::Enter(join_path(&path, &name), name));
+                }
+            }
+        }
+    }
+    exprs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::parse_input;
+
+    #[test]
+    pub fn reads_a_directory() {
+        let root = std::env::temp_dir().join(format!("day07-{}", std::process::id()));
+        fs::create_dir_all(root.join("a/e")).unwrap();
+        fs::create_dir_all(root.join("d")).unwrap();
+        fs::write(root.join("b.txt"), vec![0; 1500]).unwrap();
+        fs::write(root.join("a/f"), "hello").unwrap();
+        fs::write(root.join("a/e/i"), "").unwrap();
+        let tree = read_dir_tree(&root);
+        fs::remove_dir_all(&root).unwrap();
+        let tree = tree.unwrap();
+
+        assert_eq!(
+            vec![
+                ("/".to_string(), 1505),
+                ("/a".to_string(), 5),
+                ("/a/e".to_string(), 0),
+                ("/d".to_string(), 0)
+            ],
+            tree.dirs_sized()
+        );
+        assert_eq!(vec![Entry::File(0, "i".to_string())], tree.ls("/a/e"));
+        assert!(read_dir_tree(std::env::temp_dir().join("day07-missing")).is_err());
+    }
+
+    #[test]
+    pub fn transcripts_parse_back_to_the_same_tree() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        let tree: NodeTree = parse_input(input).unwrap().into();
+        let session: Vec<String> = transcript(&tree).iter().map(|e| e.to_string()).collect();
+        assert_eq!("$ cd /", session[0]);
+        assert_eq!("$ ls", session[1]);
+        let again: NodeTree = parse_input(session).unwrap().into();
+        assert_eq!(tree.dirs_sized(), again.dirs_sized());
+        for dir in tree.dirs() {
+            assert_eq!(tree.ls(&dir), again.ls(&dir));
+        }
+    }
+}
@@ -1,51 +1,268 @@
-use anyhow::{Error};
+use std::fmt::{Display, Formatter};
 
+use anyhow::{anyhow, Error};
 
-mod parser;
-mod with_map;
-mod with_trie;
+use crate::day07::disk::Disk;
+use crate::day07::query::DirQuery;
+
+pub mod diff;
+pub mod disk;
+pub mod from_disk;
+pub mod parser;
+pub mod query;
+pub mod render;
+pub mod shell;
+pub mod validate;
+pub mod with_map;
+pub mod with_nodes;
+pub mod with_trie;
 
 pub trait Day07 {
     fn part_one(&self) -> String;
-    fn part_two(&self) -> String;
+    fn part_two(&self, disk: &Disk) -> Result<String, Error>;
+}
+
+/// What `ls` shows for a directory.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Entry {
+    Dir(String),
+    File(u64, String),
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        match self {
+            Entry::Dir(name) => name,
+            Entry::File(_, name) => name,
+        }
+    }
+}
+
+/// A file system reconstructed from the terminal output.
+/// Directories are absolute paths like `/` or `/a/e`.
+pub trait FileTree {
+    /// The size of all files in the directory and below it.
+    fn dir_size(&self, dir: &str) -> u64;
+    /// All directories, including the root.
+    fn dirs(&self) -> Vec<String>;
+    /// The files and directories directly inside the directory, sorted by name.
+    fn ls(&self, dir: &str) -> Vec<Entry>;
+
+    fn dirs_sized(&self) -> Vec<(String, u64)> {
+        self.dirs()
+            .into_iter()
+            .map(|dir| {
+                let size = self.dir_size(&dir);
+                (dir, size)
+            })
+            .collect()
+    }
+
+    /// The directories matching the query with their sizes, sorted by path.
+    fn query(&self, query: &DirQuery) -> Vec<(String, u64)> {
+        let mut dirs: Vec<(String, u64)> = self
+            .dirs_sized()
+            .into_iter()
+            .filter(|(dir, size)| query.matches(dir, *size))
+            .collect();
+        dirs.sort();
+        dirs
+    }
+}
+
+impl<T: FileTree> Day07 for T {
+    fn part_one(&self) -> String {
+        let sum: u64 = self
+            .query(&DirQuery::new().max_size(100000))
+            .into_iter()
+            .map(|(_name, size)| size)
+            .sum();
+        format!("Sum of dirs is {}", sum)
+    }
+
+    fn part_two(&self, disk: &Disk) -> Result<String, Error> {
+        let (_dir, size) = disk
+            .smallest_dir_to_delete(self)?
+            .ok_or_else(|| anyhow!("No directory frees enough space"))?;
+        Ok(format!("Min sufficient size is: {}", size))
+    }
 }
 
 pub fn part1(input: Vec<String>) -> Result<String, Error> {
-    let expressions: Vec<Expr> = parse_input(input).unwrap();
+    let expressions: Vec<Expr> = parse_input(input)?;
 
-    let files: with_trie::FileTree = expressions.into();
-    //
-    // let files = create_file_tree(expressions);
-    // println!("{}", &files);
-    // let sum: u64 = files.part1();
+    let files = with_nodes::NodeTree::replay(expressions)?;
     Ok(files.part_one())
 }
 
 pub fn part2(input: Vec<String>) -> Result<String, Error> {
-    let expressions: Vec<Expr> = parse_input(input).unwrap();
-    // let files = create_file_tree(expressions);
-    // let sum: u64 = files.part2();
-    let files: with_trie::FileTree = expressions.into();
-    Ok(files.part_two())
+    let expressions: Vec<Expr> = parse_input(input)?;
+    let files = with_nodes::NodeTree::replay(expressions)?;
+    files.part_two(&Disk::default())
 }
 
 use crate::day07::parser::parse_input;
 
-
-
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum Expr {
     Cd(String),
     Ls,
     Dir(String),
     File(u64, String),
+    /// Removes a file or a directory, paths are relative to the working directory.
+    Rm(String),
+    Mkdir(String),
+    /// Moves or renames the first path to the second.
+    Mv(String, String),
+    /// Creates a file with the size, or changes the size of an existing file.
+    Touch(u64, String),
+}
+
+/// An absolute, normalized path for a path relative to `pwd`.
+pub fn resolve(pwd: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = if path.starts_with('/') {
+        vec![]
+    } else {
+        pwd.split('/').filter(|p| !p.is_empty()).collect()
+    };
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            name => parts.push(name),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+// the directory of an absolute path and the last name in it
+pub(crate) fn split_path(path: &str) -> Option<(&str, &str)> {
+    let (dir, name) = path.rsplit_once('/')?;
+    if name.is_empty() {
+        None
+    } else if dir.is_empty() {
+        Some(("/", name))
+    } else {
+        Some((dir, name))
+    }
+}
+
+// the path of `name` inside the directory
+pub(crate) fn join_path(dir: &str, name: &str) -> String {
+    if dir == "/" {
+        format!("/{}", name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// the entry at an absolute path, from the listing of its directory
+pub(crate) fn lookup<T: FileTree + ?Sized>(tree: &T, path: &str) -> Option<Entry> {
+    if path == "/" {
+        return Some(Entry::Dir("/".to_string()));
+    }
+    let (dir, name) = split_path(path)?;
+    tree.ls(dir).into_iter().find(|entry| entry.name() == name)
+}
+
+fn is_dir<T: FileTree + ?Sized>(tree: &T, path: &str) -> bool {
+    matches!(lookup(tree, path), Some(Entry::Dir(_)))
+}
+
+// the directory a new entry goes into has to exist
+fn check_parent<T: FileTree + ?Sized>(tree: &T, path: &str) -> Result<(), Error> {
+    let (dir, _) = split_path(path).ok_or_else(|| anyhow!("{}: not a path below /", path))?;
+    if is_dir(tree, dir) {
+        Ok(())
+    } else {
+        Err(anyhow!("{}: no such directory", dir))
+    }
+}
+
+// the checks `NodeTree` does before a change, for the trees that can not do them on the way
+pub(crate) fn check_rm<T: FileTree + ?Sized>(tree: &T, path: &str) -> Result<(), Error> {
+    match lookup(tree, path) {
+        _ if path == "/" => Err(anyhow!("rm: can not remove /")),
+        Some(_) => Ok(()),
+        None => Err(anyhow!("rm: {}: no such file or directory", path)),
+    }
 }
 
+pub(crate) fn check_mkdir<T: FileTree + ?Sized>(tree: &T, path: &str) -> Result<(), Error> {
+    check_parent(tree, path)?;
+    match lookup(tree, path) {
+        Some(_) => Err(anyhow!("mkdir: {}: already exists", path)),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn check_touch<T: FileTree + ?Sized>(tree: &T, path: &str) -> Result<(), Error> {
+    check_parent(tree, path)?;
+    if is_dir(tree, path) {
+        Err(anyhow!("touch: {}: is a directory", path))
+    } else {
+        Ok(())
+    }
+}
+
+// where `from` ends up, `None` when it stays where it is. A file there may be replaced.
+pub(crate) fn check_mv<T: FileTree + ?Sized>(
+    tree: &T,
+    from: &str,
+    to: &str,
+) -> Result<Option<String>, Error> {
+    let moved = match lookup(tree, from) {
+        _ if from == "/" => return Err(anyhow!("mv: can not move /")),
+        Some(entry) => entry,
+        None => return Err(anyhow!("mv: {}: no such file or directory", from)),
+    };
+    let target = if is_dir(tree, to) {
+        join_path(to, moved.name())
+    } else {
+        check_parent(tree, to)?;
+        to.to_string()
+    };
+    if target == from {
+        return Ok(None);
+    }
+    if target.starts_with(&format!("{}/", from)) {
+        return Err(anyhow!("mv: can not move {} into itself", from));
+    }
+    match (lookup(tree, &target), moved) {
+        (Some(Entry::File(..)), Entry::Dir(_)) => {
+            Err(anyhow!("mv: can not replace a file with a directory"))
+        }
+        (Some(Entry::Dir(_)), _) => Err(anyhow!("mv: {}: already exists", target)),
+        _ => Ok(Some(target)),
+    }
+}
+
+// the terminal line the expression was parsed from
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Cd(dir) => write!(f, "$ cd {}", dir),
+            Expr::Ls => write!(f, "$ ls"),
+            Expr::Dir(name) => write!(f, "dir {}", name),
+            Expr::File(size, name) => write!(f, "{} {}", size, name),
+            Expr::Rm(path) => write!(f, "$ rm {}", path),
+            Expr::Mkdir(path) => write!(f, "$ mkdir {}", path),
+            Expr::Mv(from, to) => write!(f, "$ mv {} {}", from, to),
+            Expr::Touch(size, path) => write!(f, "$ touch {} {}", size, path),
+        }
+    }
+}
+
+// the example from the puzzle, shared by the tests of all modules
 #[cfg(test)]
-mod tests {
-    use super::*;
+pub(crate) mod fixtures {
+    use crate::day07::parse_input;
+    use crate::day07::with_nodes::NodeTree;
+    use crate::day07::Expr;
 
-    const TEST_INPUT: &'static str = r###"$ cd /
+    pub const EXAMPLE: &str = r###"$ cd /
 $ ls
 dir a
 14848514 b.txt
@@ -69,4 +286,272 @@ $ ls
 5626152 d.ext
 7214296 k"###;
 
+    pub fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(|l| l.to_string()).collect()
+    }
+
+    pub fn parse(text: &str) -> Vec<Expr> {
+        parse_input(lines(text)).unwrap()
+    }
+
+    pub fn example_exprs() -> Vec<Expr> {
+        parse(EXAMPLE)
+    }
+
+    pub fn example() -> NodeTree {
+        example_exprs().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{example_exprs, parse};
+    use super::*;
+
+    #[test]
+    pub fn all_trees_solve_the_example_exprs() {
+        let map_tree = with_map::create_file_tree(example_exprs());
+        let trie_tree: with_trie::TrieTree = example_exprs().into();
+        let node_tree: with_nodes::NodeTree = example_exprs().into();
+        for tree in [&map_tree as &dyn Day07, &trie_tree, &node_tree] {
+            assert_eq!("Sum of dirs is 95437", tree.part_one());
+            assert_eq!(
+                "Min sufficient size is: 24933642",
+                tree.part_two(&Disk::default()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    pub fn parts_report_parse_errors() {
+        let input = vec!["$ cd /".to_string(), "$ ls".to_string(), "dir".to_string()];
+        let err = part1(input.clone()).unwrap_err().to_string();
+        assert!(err.starts_with("Failed to parse the session"), "{}", err);
+        assert!(part2(input).is_err());
+    }
+
+    #[test]
+    pub fn all_trees_list_the_same_entries() {
+        let map_tree = with_map::create_file_tree(example_exprs());
+        let trie_tree: with_trie::TrieTree = example_exprs().into();
+        let node_tree: with_nodes::NodeTree = example_exprs().into();
+        assert_eq!(
+            vec![
+                Entry::Dir("a".to_string()),
+                Entry::File(14848514, "b.txt".to_string()),
+                Entry::File(8504156, "c.dat".to_string()),
+                Entry::Dir("d".to_string()),
+            ],
+            map_tree.ls("/")
+        );
+        for dir in map_tree.dirs() {
+            assert_eq!(map_tree.ls(&dir), trie_tree.ls(&dir), "ls {}", dir);
+            assert_eq!(map_tree.ls(&dir), node_tree.ls(&dir), "ls {}", dir);
+        }
+    }
+
+    #[test]
+    pub fn empty_files_and_shared_prefixes() {
+        let session = "$ cd /\n$ ls\ndir a\ndir ab\n0 empty\n$ cd a\n$ ls\n10 x\n0 y\n$ cd ..\n$ cd ab\n$ ls\n100 z";
+        let exprs = || parse(session);
+        let map_tree = with_map::create_file_tree(exprs());
+        let trie_tree: with_trie::TrieTree = exprs().into();
+        let node_tree: with_nodes::NodeTree = exprs().into();
+        for tree in [&map_tree as &dyn FileTree, &trie_tree, &node_tree] {
+            let mut dirs = tree.dirs_sized();
+            dirs.sort();
+            assert_eq!(
+                vec![
+                    ("/".to_string(), 110),
+                    ("/a".to_string(), 10),
+                    ("/ab".to_string(), 100)
+                ],
+                dirs
+            );
+            assert_eq!(
+                vec![
+                    Entry::Dir("a".to_string()),
+                    Entry::Dir("ab".to_string()),
+                    Entry::File(0, "empty".to_string()),
+                ],
+                tree.ls("/")
+            );
+            assert_eq!(
+                vec![
+                    Entry::File(10, "x".to_string()),
+                    Entry::File(0, "y".to_string())
+                ],
+                tree.ls("/a")
+            );
+        }
+    }
+
+    #[test]
+    pub fn all_trees_apply_changes() {
+        let mut exprs = example_exprs();
+        let changes = "$ cd /\n$ rm b.txt\n$ mkdir x\n$ cd a\n$ mv e /x\n$ touch 5 g\n$ touch 7 ../x/e/new\n$ mv /d/k k2\n$ rm /d";
+        exprs.extend(parse(changes));
+        let map_tree = with_map::create_file_tree(exprs.clone());
+        let trie_tree: with_trie::TrieTree = exprs.clone().into();
+        let node_tree: with_nodes::NodeTree = exprs.clone().into();
+        let expected = vec![
+            (
+                "/".to_string(),
+                8504156 + 29116 + 5 + 62596 + 7214296 + 584 + 7,
+            ),
+            ("/a".to_string(), 29116 + 5 + 62596 + 7214296),
+            ("/x".to_string(), 591),
+            ("/x/e".to_string(), 591),
+        ];
+        for tree in [&map_tree as &dyn FileTree, &trie_tree, &node_tree] {
+            let mut dirs = tree.dirs_sized();
+            dirs.sort();
+            assert_eq!(expected, dirs);
+            assert_eq!(node_tree.ls("/a"), tree.ls("/a"));
+        }
+
+        // changes that make no sense fail on every tree and are skipped
+        let mut map_tree = map_tree;
+        let mut trie_tree = trie_tree;
+        let mut node_tree = node_tree;
+        let failing = [
+            (
+                map_tree.touch("/nope/x", 5),
+                trie_tree.touch("/nope/x", 5),
+                node_tree.touch("/nope/x", 5).map(|_| ()),
+            ),
+            (
+                map_tree.mkdir("/c.dat"),
+                trie_tree.mkdir("/c.dat"),
+                node_tree.mkdir("/c.dat").map(|_| ()),
+            ),
+            (
+                map_tree.mkdir("/x/e"),
+                trie_tree.mkdir("/x/e"),
+                node_tree.mkdir("/x/e").map(|_| ()),
+            ),
+            (
+                map_tree.mv("/x/e", "/c.dat"),
+                trie_tree.mv("/x/e", "/c.dat"),
+                node_tree.mv("/x/e", "/c.dat"),
+            ),
+            (
+                map_tree.touch("/x", 1),
+                trie_tree.touch("/x", 1),
+                node_tree.touch("/x", 1).map(|_| ()),
+            ),
+            (
+                map_tree.mv("/x", "/x/e"),
+                trie_tree.mv("/x", "/x/e"),
+                node_tree.mv("/x", "/x/e"),
+            ),
+            (
+                map_tree.rm("/nope"),
+                trie_tree.rm("/nope"),
+                node_tree.rm("/nope"),
+            ),
+            (map_tree.rm("/"), trie_tree.rm("/"), node_tree.rm("/")),
+        ];
+        for (map, trie, node) in failing {
+            let error = node.unwrap_err().to_string();
+            assert_eq!(error, map.unwrap_err().to_string());
+            assert_eq!(error, trie.unwrap_err().to_string());
+        }
+        let failing = "$ cd /\n$ touch 5 /nope/x\n$ mkdir c.dat\n$ mv x/e c.dat\n$ touch 1 x\n$ cd x/e/../e\n$ ls\n3 y\n$ cd ../..\n$ ls\n2 z";
+        exprs.extend(parse(failing));
+        let map_tree = with_map::create_file_tree(exprs.clone());
+        let trie_tree: with_trie::TrieTree = exprs.clone().into();
+        let node_tree: with_nodes::NodeTree = exprs.into();
+        let mut expected = node_tree.dirs_sized();
+        expected.sort();
+        assert_eq!(("/".to_string(), 15810765), expected[0]);
+        assert_eq!(("/x/e".to_string(), 594), expected[3]);
+        for tree in [&map_tree as &dyn FileTree, &trie_tree] {
+            let mut dirs = tree.dirs_sized();
+            dirs.sort();
+            assert_eq!(expected, dirs);
+            for (dir, _) in &expected {
+                assert_eq!(node_tree.ls(dir), tree.ls(dir));
+            }
+        }
+    }
+
+    #[test]
+    pub fn all_trees_agree_on_the_input() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        let map_tree = with_map::create_file_tree(parse_input(input.clone()).unwrap());
+        let trie_tree: with_trie::TrieTree = parse_input(input.clone()).unwrap().into();
+        let node_tree: with_nodes::NodeTree = parse_input(input).unwrap().into();
+
+        let mut map_dirs = map_tree.dirs_sized();
+        let mut trie_dirs = trie_tree.dirs_sized();
+        let mut node_dirs = node_tree.dirs_sized();
+        map_dirs.sort();
+        trie_dirs.sort();
+        node_dirs.sort();
+        assert_eq!(map_dirs, trie_dirs);
+        assert_eq!(map_dirs, node_dirs);
+    }
+
+    #[test]
+    pub fn all_trees_answer_queries() {
+        let map_tree = with_map::create_file_tree(example_exprs());
+        let trie_tree: with_trie::TrieTree = example_exprs().into();
+        let query = DirQuery::new().min_depth(1).max_size(100000);
+        let expected = vec![("/a".to_string(), 94853), ("/a/e".to_string(), 584)];
+        assert_eq!(expected, map_tree.query(&query));
+        assert_eq!(expected, trie_tree.query(&query));
+
+        let query = DirQuery::new()
+            .name_matching("^[de]$")
+            .unwrap()
+            .max_depth(1);
+        assert_eq!(vec![("/d".to_string(), 24933642)], trie_tree.query(&query));
+        assert_eq!(
+            vec![94853, 584],
+            map_tree
+                .find_files_smaller_then(100000)
+                .collect::<Vec<u64>>()
+        );
+        assert_eq!(
+            vec![584],
+            map_tree.find_files_smaller_then(1000).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    pub fn disk_is_configurable() {
+        let tree: with_trie::TrieTree = example_exprs().into();
+        assert_eq!(
+            "Min sufficient size is: 94853",
+            tree.part_two(&Disk::new(48381165, 90000)).unwrap()
+        );
+        assert!(tree.part_two(&Disk::new(40000000, 0)).is_err());
+        // `/a` and `/a/e` together would be enough, but `/a/e` is deleted with `/a`
+        assert_eq!(
+            vec![("/d".to_string(), 24933642)],
+            Disk::new(48381165, 95400).cleanup_plan(&tree).unwrap().dirs
+        );
+        assert!(tree.part_two(&Disk::new(70000000, 80000000)).is_err());
+    }
+
+    #[test]
+    pub fn cleanup_plan_on_the_input() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        let tree = with_map::create_file_tree(parse_input(input).unwrap());
+        let disk = Disk::default();
+        let needed = disk.space_to_free(tree.dir_size("/")).unwrap();
+        let (_dir, single) = disk.smallest_dir_to_delete(&tree).unwrap().unwrap();
+        let plan = disk.cleanup_plan(&tree).unwrap();
+        assert!(plan.freed >= needed && plan.freed <= single);
+        assert_eq!(
+            plan.freed,
+            plan.dirs.iter().map(|(_, size)| size).sum::<u64>()
+        );
+        for (a, _) in &plan.dirs {
+            for (b, _) in &plan.dirs {
+                assert!(a == b || !b.starts_with(&format!("{}/", a)));
+            }
+        }
+    }
 }
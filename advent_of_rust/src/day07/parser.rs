@@ -1,66 +1,338 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
 use anyhow::{anyhow, Error};
 
-use chumsky::{Parser};
+use chumsky::error::SimpleReason;
+use chumsky::Parser;
 
+use crate::day07::Expr;
+use chumsky::prelude::*;
 
+/// A command from the session together with what it printed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Command {
+    Cd(String),
+    /// The listed entries, each an `Expr::Dir` or `Expr::File`.
+    Ls(Vec<Expr>),
+    Rm(String),
+    Mkdir(String),
+    Mv(String, String),
+    Touch(u64, String),
+    /// Any other command, kept as it was typed.
+    Unknown {
+        line: String,
+        output: Vec<String>,
+    },
+}
 
+/// Where and why a session could not be parsed. Lines and columns start at 1.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SessionError {
+    pub span: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
 
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
 
-use crate::day07::Expr;
-use chumsky::prelude::*;
+impl std::error::Error for SessionError {}
 
+impl SessionError {
+    fn new(input: &str, error: Simple<char>) -> Self {
+        let span = error.span();
+        let before: String = input.chars().take(span.start).collect();
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        let found = match error.found() {
+            Some('\n') => "end of line".to_string(),
+            Some(c) => format!("'{}'", c),
+            None => "end of input".to_string(),
+        };
+        let mut expected: Vec<String> = error
+            .expected()
+            .map(|c| match c {
+                Some('\n') => "end of line".to_string(),
+                Some(c) => format!("'{}'", c),
+                None => "end of input".to_string(),
+            })
+            .collect();
+        expected.sort();
+        expected.dedup();
+        let message = match error.reason() {
+            SimpleReason::Custom(message) => message.clone(),
+            _ if expected.is_empty() => format!("unexpected {}", found),
+            _ => format!("expected {} but found {}", expected.join(" or "), found),
+        };
+        let message = match error.label() {
+            Some(label) => format!("{} in {}", message, label),
+            None => message,
+        };
+        SessionError {
+            span,
+            line,
+            column,
+            message,
+        }
+    }
+}
 
-pub fn parse_input(input: Vec<String>) -> Result<Vec<Expr>, Error> {
-    let mut res = vec![];
-    let parser = parser();
-    for (idx, line) in input.iter().enumerate() {
-        res.push(
-            parser
-                .parse(line.trim())
-                .map_err(|_err| anyhow!("Failed to parse line {}:{}", idx, &line))?,
-        );
+/// Parses a whole terminal session, reporting every error with its position.
+pub fn parse_session(input: &str) -> Result<Vec<Command>, Vec<SessionError>> {
+    session().parse(input).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|error| SessionError::new(input, error))
+            .collect()
+    })
+}
+
+/// The commands as a flat list of expressions, unknown commands are left out.
+pub fn to_exprs(commands: Vec<Command>) -> Vec<Expr> {
+    let mut exprs = vec![];
+    for command in commands {
+        match command {
+            Command::Cd(dir) => exprs.push(Expr::Cd(dir)),
+            Command::Ls(entries) => {
+                exprs.push(Expr::Ls);
+                exprs.extend(entries);
+            }
+            Command::Rm(path) => exprs.push(Expr::Rm(path)),
+            Command::Mkdir(path) => exprs.push(Expr::Mkdir(path)),
+            Command::Mv(from, to) => exprs.push(Expr::Mv(from, to)),
+            Command::Touch(size, path) => exprs.push(Expr::Touch(size, path)),
+            Command::Unknown { .. } => {}
+        }
     }
-    Ok(res)
+    exprs
 }
 
-// TODO _ I dont get how to repeat this with newlines!?
-fn parser() -> impl Parser<char, Expr, Error = Simple<char>> {
-    let command = just('$').padded();
+pub fn parse_input(input: Vec<String>) -> Result<Vec<Expr>, Error> {
+    let commands = parse_session(&input.join("\n")).map_err(|errors| {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        anyhow!("Failed to parse the session: {}", errors.join(", "))
+    })?;
+    Ok(to_exprs(commands))
+}
+
+fn session() -> impl Parser<char, Vec<Command>, Error = Simple<char>> {
+    let space = one_of(" \t").repeated();
+    let gap = one_of(" \t").repeated().at_least(1);
+    // the line has to end here, without taking the newline
+    let end_of_line = space
+        .clone()
+        .then(text::newline().or(end()))
+        .rewind()
+        .labelled("end of line");
 
-    let file_name = filter(|c: &char| !c.is_ascii_whitespace())
+    let name = filter(|c: &char| !c.is_whitespace())
         .repeated()
         .at_least(1)
-        .then_ignore(end()) // why?
-        .labelled("file-name")
-        .collect::<String>();
+        .collect::<String>()
+        .labelled("name");
 
     let int = text::int(10)
-        .map(|x: String| x.parse::<u64>().unwrap())
-        .labelled("integer");
+        .try_map(|x: String, span| {
+            x.parse::<u64>()
+                .map_err(|err| Simple::custom(span, format!("invalid size {}: {}", x, err)))
+        })
+        .labelled("size");
 
-    let cd = command
-        .clone()
-        .then(text::keyword("cd"))
-        .padded()
-        .ignore_then(file_name.clone())
-        .labelled("cd")
-        .map(|file_name| Expr::Cd(file_name));
+    let prompt = just('$').then(space.clone());
 
-    let ls = command
+    let cd = prompt
         .clone()
-        .then(text::keyword("ls"))
-        .labelled("ls")
-        .map(|_| Expr::Ls);
+        .ignore_then(text::keyword("cd"))
+        .ignore_then(gap.clone())
+        .ignore_then(name)
+        .then_ignore(end_of_line.clone())
+        .labelled("cd")
+        .map(Command::Cd);
 
     let dir = text::keyword("dir")
-        .padded()
-        .ignore_then(file_name.clone())
-        .labelled("dir")
-        .map(|file| Expr::Dir(file));
+        .ignore_then(gap.clone())
+        .ignore_then(name)
+        .map(Expr::Dir);
 
     let file = int
-        .padded()
-        .then(file_name.clone())
+        .then_ignore(gap.clone())
+        .then(name)
         .map(|(size, name)| Expr::File(size, name));
-    choice((cd, ls, dir, file))
+
+    let entry = dir
+        .or(file)
+        .then_ignore(end_of_line.clone())
+        .labelled("ls output");
+
+    let ls = prompt
+        .clone()
+        .ignore_then(text::keyword("ls"))
+        .ignore_then(end_of_line.clone())
+        .ignore_then(text::newline().ignore_then(entry).repeated())
+        .labelled("ls")
+        .map(Command::Ls);
+
+    let rm = prompt
+        .clone()
+        .ignore_then(text::keyword("rm"))
+        .ignore_then(gap.clone())
+        .ignore_then(
+            just('-')
+                .then(one_of("rf").repeated().at_least(1))
+                .then(gap.clone())
+                .or_not(),
+        )
+        .ignore_then(name)
+        .then_ignore(end_of_line.clone())
+        .labelled("rm")
+        .map(Command::Rm);
+
+    let mkdir = prompt
+        .clone()
+        .ignore_then(text::keyword("mkdir"))
+        .ignore_then(gap.clone())
+        .ignore_then(name)
+        .then_ignore(end_of_line.clone())
+        .labelled("mkdir")
+        .map(Command::Mkdir);
+
+    let mv = prompt
+        .clone()
+        .ignore_then(text::keyword("mv"))
+        .ignore_then(gap.clone())
+        .ignore_then(name)
+        .then_ignore(gap.clone())
+        .then(name)
+        .then_ignore(end_of_line.clone())
+        .labelled("mv")
+        .map(|(from, to)| Command::Mv(from, to));
+
+    let touch = prompt
+        .clone()
+        .ignore_then(text::keyword("touch"))
+        .ignore_then(gap.clone())
+        .ignore_then(int)
+        .then_ignore(gap.clone())
+        .then(name)
+        .then_ignore(end_of_line.clone())
+        .labelled("touch")
+        .map(|(size, path)| Command::Touch(size, path));
+
+    let rest_of_line = filter(|c: &char| *c != '\n' && *c != '\r').repeated();
+
+    let output_line = filter(|c: &char| *c != '$' && *c != '\n' && *c != '\r')
+        .chain(rest_of_line)
+        .collect::<String>();
+
+    let unknown = prompt
+        .clone()
+        .ignore_then(rest_of_line.at_least(1).collect::<String>())
+        .then(text::newline().ignore_then(output_line).repeated())
+        .map(|(line, output)| Command::Unknown {
+            line: line.trim_end().to_string(),
+            output,
+        });
+
+    text::whitespace()
+        .ignore_then(
+            choice((cd, ls, rm, mkdir, mv, touch, unknown))
+                .separated_by(text::newline().repeated().at_least(1))
+                .allow_trailing(),
+        )
+        .then_ignore(end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn groups_commands_with_their_output() {
+        let session =
+            "$ cd /\n$ ls\ndir a\n14848514 b.txt\n$ git status\nOn branch main\n\n$ cd a\n$ ls\n";
+        assert_eq!(
+            Ok(vec![
+                Command::Cd("/".to_string()),
+                Command::Ls(vec![
+                    Expr::Dir("a".to_string()),
+                    Expr::File(14848514, "b.txt".to_string())
+                ]),
+                Command::Unknown {
+                    line: "git status".to_string(),
+                    output: vec!["On branch main".to_string()]
+                },
+                Command::Cd("a".to_string()),
+                Command::Ls(vec![]),
+            ]),
+            parse_session(session)
+        );
+    }
+
+    #[test]
+    pub fn parses_changes() {
+        let session =
+            "$ rm -rf a\n$ rm b.txt\n$ mkdir x\n$ mv a/e /d\n$ touch 100 notes\n$ touch notes";
+        let commands = parse_session(session).unwrap();
+        assert_eq!(
+            vec![
+                Expr::Rm("a".to_string()),
+                Expr::Rm("b.txt".to_string()),
+                Expr::Mkdir("x".to_string()),
+                Expr::Mv("a/e".to_string(), "/d".to_string()),
+                Expr::Touch(100, "notes".to_string()),
+            ],
+            to_exprs(commands.clone())
+        );
+        // a touch without a size is not ours
+        assert_eq!(
+            Command::Unknown {
+                line: "touch notes".to_string(),
+                output: vec![]
+            },
+            commands[5]
+        );
+        for expr in to_exprs(commands) {
+            assert_eq!(
+                vec![expr.clone()],
+                parse_input(vec![expr.to_string()]).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    pub fn parses_the_input_in_one_pass() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        let exprs = parse_input(input.clone()).unwrap();
+        assert_eq!(input.len(), exprs.len());
+        assert_eq!(Expr::Cd("/".to_string()), exprs[0]);
+    }
+
+    #[test]
+    pub fn reports_where_parsing_failed() {
+        let errors = parse_session("$ cd /\n$ ls\ndir a\n12x b.txt\n").unwrap_err();
+        assert_eq!(4, errors[0].line);
+        assert_eq!(3, errors[0].column);
+        assert_eq!(20..21, errors[0].span);
+
+        let errors = parse_session("$ ls\ndir").unwrap_err();
+        assert_eq!((2, 4), (errors[0].line, errors[0].column));
+        // a cd that is not quite a cd is passed through
+        assert_eq!(
+            Ok(vec![Command::Unknown {
+                line: "cd a b".to_string(),
+                output: vec![]
+            }]),
+            parse_session("$ cd a b")
+        );
+        assert!(parse_input(vec!["$ ls".to_string(), "dir".to_string()]).is_err());
+    }
 }
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Error};
+use regex::Regex;
+
+/// Selects directories by their size, depth and name. Bounds that are not set match all.
+#[derive(Debug, Clone, Default)]
+pub struct DirQuery {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    name: Option<Regex>,
+}
+
+impl DirQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = Some(depth);
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Only directories whose own name matches the regular expression. The root is called `/`.
+    pub fn name_matching(mut self, pattern: &str) -> Result<Self, Error> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| anyhow!("Invalid name pattern '{}': {}", pattern, e))?;
+        self.name = Some(regex);
+        Ok(self)
+    }
+
+    pub fn matches(&self, dir: &str, size: u64) -> bool {
+        let depth = depth(dir);
+        self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+            && self.min_depth.is_none_or(|min| depth >= min)
+            && self.max_depth.is_none_or(|max| depth <= max)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(name(dir)))
+    }
+}
+
+/// How many directories are above, the root has depth 0.
+pub fn depth(dir: &str) -> usize {
+    dir.split('/').filter(|part| !part.is_empty()).count()
+}
+
+pub fn name(dir: &str) -> &str {
+    match dir.rsplit('/').next() {
+        Some(name) if !name.is_empty() => name,
+        _ => "/",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn depth_and_name() {
+        assert_eq!((0, "/"), (depth("/"), name("/")));
+        assert_eq!((1, "a"), (depth("/a"), name("/a")));
+        assert_eq!((2, "e"), (depth("/a/e"), name("/a/e")));
+    }
+
+    #[test]
+    pub fn all_bounds_apply() {
+        let query = DirQuery::new()
+            .min_size(10)
+            .max_size(100)
+            .min_depth(1)
+            .max_depth(2)
+            .name_matching("^a")
+            .unwrap();
+        assert!(query.matches("/b/abc", 50));
+        assert!(!query.matches("/b/abc", 5));
+        assert!(!query.matches("/b/abc", 500));
+        assert!(!query.matches("/", 50));
+        assert!(!query.matches("/a/b/abc", 50));
+        assert!(!query.matches("/a/bc", 50));
+        assert!(DirQuery::new().matches("/", 0));
+        assert!(DirQuery::new().name_matching("(").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::Error;
+use serde::Serialize;
+
+use crate::day07::{join_path, Entry, FileTree};
+
+/// Sizes the way `du -h` shows them, in powers of 1024.
+pub fn human(size: u64) -> String {
+    let units = ["K", "M", "G", "T"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, units[unit])
+    } else {
+        format!("{:.0}{}", value, units[unit])
+    }
+}
+
+/// Draws the directory like `tree` does, with the cumulative size of every directory.
+pub fn tree<T: FileTree + ?Sized>(files: &T, dir: &str) -> String {
+    let mut out = format!("{} ({})\n", dir, files.dir_size(dir));
+    // entries still to draw, with their directory and the prefix of their line
+    let below = |dir: &str, prefix: &str| {
+        let entries = files.ls(dir);
+        let count = entries.len();
+        entries
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(idx, entry)| (entry, dir.to_string(), prefix.to_string(), idx + 1 == count))
+            .collect::<Vec<_>>()
+    };
+    let mut todo = below(dir, "");
+    while let Some((entry, dir, prefix, last)) = todo.pop() {
+        let branch = if last { "└── " } else { "├── " };
+        match entry {
+            Entry::Dir(name) => {
+                let path = join_path(&dir, &name);
+                out.push_str(&format!(
+                    "{}{}{}/ ({})\n",
+                    prefix,
+                    branch,
+                    name,
+                    files.dir_size(&path)
+                ));
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                todo.extend(below(&path, &prefix));
+            }
+            Entry::File(size, name) => {
+                out.push_str(&format!("{}{}{} ({})\n", prefix, branch, name, size))
+            }
+        }
+    }
+    out
+}
+
+/// Every directory inside `dir` and `dir` itself, biggest first, like `du | sort -rn`.
+pub fn du<T: FileTree + ?Sized>(files: &T, dir: &str, human_sizes: bool) -> String {
+    let mut dirs: Vec<(String, u64)> = files
+        .dirs_sized()
+        .into_iter()
+        .filter(|(path, _)| dir == "/" || path == dir || path.starts_with(&format!("{}/", dir)))
+        .collect();
+    dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    dirs.into_iter()
+        .map(|(path, size)| {
+            let size = if human_sizes {
+                human(size)
+            } else {
+                size.to_string()
+            };
+            format!("{}\t{}\n", size, path)
+        })
+        .collect()
+}
+
+/// A file or directory in the JSON export.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct JsonNode {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<JsonNode>>,
+}
+
+// the default drop recurses once per level, deep trees would overflow the stack
+impl Drop for JsonNode {
+    fn drop(&mut self) {
+        let mut todo = self.children.take().unwrap_or_default();
+        while let Some(mut node) = todo.pop() {
+            todo.extend(node.children.take().unwrap_or_default());
+        }
+    }
+}
+
+/// The hierarchy below `dir`, directories with their cumulative size.
+pub fn json_nodes<T: FileTree + ?Sized>(files: &T, dir: &str) -> JsonNode {
+    let dir_node = |name: String, path: &str| JsonNode {
+        name,
+        kind: "dir",
+        size: files.dir_size(path),
+        children: None,
+    };
+    // the directories being filled, deepest last, with the entries still to add
+    let mut open = vec![(
+        dir_node(dir.to_string(), dir),
+        files.ls(dir).into_iter(),
+        dir.to_string(),
+        vec![],
+    )];
+    loop {
+        let (_, entries, path, children) = open.last_mut().expect("the root is open");
+        match entries.next() {
+            Some(Entry::File(size, name)) => children.push(JsonNode {
+                name,
+                kind: "file",
+                size,
+                children: None,
+            }),
+            Some(Entry::Dir(name)) => {
+                let path = join_path(path, &name);
+                let entries = files.ls(&path).into_iter();
+                open.push((dir_node(name, &path), entries, path, vec![]));
+            }
+            None => {
+                let (mut node, _, _, children) = open.pop().expect("the root is open");
+                node.children = Some(children);
+                match open.last_mut() {
+                    Some((_, _, _, siblings)) => siblings.push(node),
+                    None => return node,
+                }
+            }
+        }
+    }
+}
+
+// a node still to write with its indentation and what follows it, or the end of
+// the children of a directory
+enum Json<'a> {
+    Node(&'a JsonNode, usize, &'static str),
+    Close(usize, &'static str),
+}
+
+/// The export as pretty printed JSON, written like `serde_json::to_string_pretty`
+/// but without recursion so deep trees are fine.
+pub fn to_json<T: FileTree + ?Sized>(files: &T, dir: &str) -> Result<String, Error> {
+    let root = json_nodes(files, dir);
+    let mut out = String::new();
+    let mut todo = vec![Json::Node(&root, 0, "")];
+    while let Some(step) = todo.pop() {
+        match step {
+            Json::Node(node, indent, after) => {
+                let pad = " ".repeat(indent + 2);
+                out.push_str(&format!("{}{{\n", " ".repeat(indent)));
+                out.push_str(&format!(
+                    "{}\"name\": {},\n",
+                    pad,
+                    serde_json::to_string(&node.name)?
+                ));
+                out.push_str(&format!("{}\"type\": \"{}\",\n", pad, node.kind));
+                out.push_str(&format!("{}\"size\": {}", pad, node.size));
+                match &node.children {
+                    Some(children) if !children.is_empty() => {
+                        out.push_str(&format!(",\n{}\"children\": [\n", pad));
+                        todo.push(Json::Close(indent, after));
+                        let last = children.len() - 1;
+                        for (idx, child) in children.iter().enumerate().rev() {
+                            let after = if idx == last { "\n" } else { ",\n" };
+                            todo.push(Json::Node(child, indent + 4, after));
+                        }
+                    }
+                    Some(_) => out.push_str(&format!(
+                        ",\n{}\"children\": []\n{}}}{}",
+                        pad,
+                        " ".repeat(indent),
+                        after
+                    )),
+                    None => out.push_str(&format!("\n{}}}{}", " ".repeat(indent), after)),
+                }
+            }
+            Json::Close(indent, after) => out.push_str(&format!(
+                "{}]\n{}}}{}",
+                " ".repeat(indent + 2),
+                " ".repeat(indent),
+                after
+            )),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::fixtures::example;
+    use crate::day07::with_nodes::NodeTree;
+
+    #[test]
+    pub fn draws_a_tree() {
+        assert_eq!(
+            r###"/ (48381165)
+├── a/ (94853)
+│   ├── e/ (584)
+│   │   └── i (584)
+│   ├── f (29116)
+│   ├── g (2557)
+│   └── h.lst (62596)
+├── b.txt (14848514)
+├── c.dat (8504156)
+└── d/ (24933642)
+    ├── d.ext (5626152)
+    ├── d.log (8033020)
+    ├── j (4060174)
+    └── k (7214296)
+"###,
+            tree(&example(), "/")
+        );
+    }
+
+    #[test]
+    pub fn lists_biggest_first() {
+        assert_eq!(
+            "46M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n",
+            du(&example(), "/", true)
+        );
+        assert_eq!("94853\t/a\n584\t/a/e\n", du(&example(), "/a", false));
+    }
+
+    #[test]
+    pub fn exports_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&example(), "/").unwrap()).unwrap();
+        assert_eq!(48381165, json["size"]);
+        assert_eq!("a", json["children"][0]["name"]);
+        assert_eq!("dir", json["children"][0]["type"]);
+        assert_eq!(584, json["children"][0]["children"][0]["size"]);
+        assert_eq!("file", json["children"][1]["type"]);
+        assert!(json["children"][1].get("children").is_none());
+    }
+
+    #[test]
+    pub fn writes_json_like_serde() {
+        let mut tree = example();
+        tree.mkdir("/empty \"dir\"").unwrap();
+        assert_eq!(
+            serde_json::to_string_pretty(&json_nodes(&tree, "/")).unwrap(),
+            to_json(&tree, "/").unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string_pretty(&json_nodes(&tree, "/a")).unwrap(),
+            to_json(&tree, "/a").unwrap()
+        );
+    }
+
+    #[test]
+    pub fn exports_deeply_nested_trees() {
+        let mut tree = NodeTree::new();
+        let mut dir = 0;
+        for _ in 0..2000 {
+            dir = tree.add_dir(dir, "d").unwrap();
+        }
+        tree.add_file(dir, "f", 7).unwrap();
+        tree.compute_sizes();
+        // a small stack, recursing once per directory would overflow it
+        let depth = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || {
+                let root = json_nodes(&tree, "/");
+                let mut node = &root;
+                let mut depth = 0;
+                while let Some(children) = &node.children {
+                    assert_eq!(7, node.size);
+                    node = &children[0];
+                    depth += 1;
+                }
+                assert_eq!("f", node.name);
+                assert!(to_json(&tree, "/").unwrap().ends_with("\n}"));
+                depth
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(2001, depth);
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Error};
+
+use crate::day07::render;
+use crate::day07::{join_path, resolve, Entry, FileTree};
+
+// `+N`, `-N` or `N` with an optional k, M or G suffix, like find's -size
+fn size_filter(arg: &str) -> Result<impl Fn(u64) -> bool, Error> {
+    let (cmp, rest) = match arg.chars().next() {
+        Some('+') => (1, &arg[1..]),
+        Some('-') => (-1, &arg[1..]),
+        _ => (0, arg),
+    };
+    let (digits, factor) = match rest.chars().last() {
+        Some('k') => (&rest[..rest.len() - 1], 1024),
+        Some('M') => (&rest[..rest.len() - 1], 1024 * 1024),
+        Some('G') => (&rest[..rest.len() - 1], 1024 * 1024 * 1024),
+        _ => (rest, 1),
+    };
+    let limit = digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|digits| digits.checked_mul(factor))
+        .ok_or_else(|| anyhow!("find: invalid size {}", arg))?;
+    Ok(move |size: u64| match cmp {
+        1 => size > limit,
+        -1 => size < limit,
+        _ => size == limit,
+    })
+}
+
+/// A tiny shell to walk around a reconstructed file tree.
+pub struct Shell<'a, T: FileTree> {
+    tree: &'a T,
+    pwd: String,
+}
+
+impl<'a, T: FileTree> Shell<'a, T> {
+    pub fn new(tree: &'a T) -> Self {
+        Shell {
+            tree,
+            pwd: "/".to_string(),
+        }
+    }
+
+    pub fn pwd(&self) -> &str {
+        &self.pwd
+    }
+
+    /// An absolute, normalized path for a path relative to the working directory.
+    pub fn resolve(&self, path: &str) -> String {
+        resolve(&self.pwd, path)
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        path == "/"
+            || self
+                .lookup(path)
+                .is_some_and(|e| matches!(e, Entry::Dir(_)))
+    }
+
+    // the entry for a path, from the listing of its parent
+    fn lookup(&self, path: &str) -> Option<Entry> {
+        let (parent, name) = path.rsplit_once('/')?;
+        let parent = if parent.is_empty() { "/" } else { parent };
+        self.tree.ls(parent).into_iter().find(|e| e.name() == name)
+    }
+
+    fn dir_arg(&self, args: &[&str]) -> Result<String, Error> {
+        let dir = self.resolve(args.first().cloned().unwrap_or("."));
+        if self.is_dir(&dir) {
+            Ok(dir)
+        } else {
+            Err(anyhow!("{}: not a directory", dir))
+        }
+    }
+
+    /// Runs one command line and returns what it prints.
+    pub fn run(&mut self, line: &str) -> Result<String, Error> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = words.split_first() else {
+            return Ok(String::new());
+        };
+        match *command {
+            "pwd" => Ok(format!("{}\n", self.pwd)),
+            "cd" => {
+                self.pwd = self.dir_arg(if args.is_empty() { &["/"] } else { args })?;
+                Ok(String::new())
+            }
+            "ls" => {
+                let dir = self.dir_arg(args)?;
+                Ok(self
+                    .tree
+                    .ls(&dir)
+                    .into_iter()
+                    .map(|entry| match entry {
+                        Entry::Dir(name) => format!("dir {}\n", name),
+                        Entry::File(size, name) => format!("{} {}\n", size, name),
+                    })
+                    .collect())
+            }
+            "du" => {
+                let human_sizes = args.first() == Some(&"-h");
+                let dir = self.dir_arg(if human_sizes { &args[1..] } else { args })?;
+                Ok(render::du(self.tree, &dir, human_sizes))
+            }
+            "find" => {
+                let (dir, filter) = match args {
+                    ["-size", size] => (self.pwd.clone(), size),
+                    [dir, "-size", size] => (self.dir_arg(&[dir])?, size),
+                    _ => return Err(anyhow!("usage: find [dir] -size [+-]N[kMG]")),
+                };
+                let filter = size_filter(filter)?;
+                let mut found = vec![];
+                let mut todo = vec![dir];
+                while let Some(dir) = todo.pop() {
+                    for entry in self.tree.ls(&dir) {
+                        match entry {
+                            Entry::Dir(name) => todo.push(join_path(&dir, &name)),
+                            Entry::File(size, name) if filter(size) => {
+                                found.push(join_path(&dir, &name))
+                            }
+                            Entry::File(_, _) => {}
+                        }
+                    }
+                }
+                found.sort();
+                Ok(found
+                    .into_iter()
+                    .map(|path| format!("{}\n", path))
+                    .collect())
+            }
+            "tree" => {
+                let dir = self.dir_arg(args)?;
+                Ok(render::tree(self.tree, &dir))
+            }
+            "stat" => {
+                let [path] = args else {
+                    return Err(anyhow!("usage: stat <path>"));
+                };
+                let path = self.resolve(path);
+                let (kind, size) = if path == "/" {
+                    ("directory", self.tree.dir_size("/"))
+                } else {
+                    match self.lookup(&path) {
+                        Some(Entry::Dir(_)) => ("directory", self.tree.dir_size(&path)),
+                        Some(Entry::File(size, _)) => ("regular file", size),
+                        None => return Err(anyhow!("stat: {}: no such file or directory", path)),
+                    }
+                };
+                Ok(format!(
+                    "  File: {}\n  Size: {}\n  Type: {}\n",
+                    path, size, kind
+                ))
+            }
+            _ => Err(anyhow!("{}: command not found", command)),
+        }
+    }
+
+    /// Reads commands until `exit` or the end of the input, errors are
+    /// printed and do not stop the shell.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> Result<(), Error> {
+        write!(output, "{} $ ", self.pwd)?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if line.trim() == "exit" {
+                break;
+            }
+            match self.run(&line) {
+                Ok(out) => write!(output, "{}", out)?,
+                Err(err) => writeln!(output, "{}", err)?,
+            }
+            write!(output, "{} $ ", self.pwd)?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::fixtures::example;
+
+    #[test]
+    pub fn walks_around() {
+        let tree = example();
+        let mut shell = Shell::new(&tree);
+        assert_eq!("", shell.run("cd a/e").unwrap());
+        assert_eq!("/a/e", shell.pwd());
+        assert_eq!("584 i\n", shell.run("ls").unwrap());
+        shell.run("cd ../..").unwrap();
+        assert_eq!("/", shell.pwd());
+        assert!(shell.run("cd b.txt").is_err());
+        assert!(shell.run("cd x").is_err());
+        assert_eq!("/", shell.pwd());
+        assert_eq!(
+            "dir e\n29116 f\n2557 g\n62596 h.lst\n",
+            shell.run("ls /a").unwrap()
+        );
+        assert!(shell.run("rm -rf /").is_err());
+    }
+
+    #[test]
+    pub fn answers_queries() {
+        let tree = example();
+        let mut shell = Shell::new(&tree);
+        assert_eq!(
+            "46M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n",
+            shell.run("du -h").unwrap()
+        );
+        assert_eq!("94853\t/a\n584\t/a/e\n", shell.run("du a").unwrap());
+        assert_eq!(
+            "/b.txt\n/c.dat\n/d/d.log\n",
+            shell.run("find -size +7M").unwrap()
+        );
+        assert_eq!("/a/e/i\n/a/g\n", shell.run("find a -size -3k").unwrap());
+        assert!(shell.run("find -size +20000000000G").is_err());
+        assert_eq!(
+            "  File: /a/h.lst\n  Size: 62596\n  Type: regular file\n",
+            shell.run("stat a/h.lst").unwrap()
+        );
+        assert_eq!(
+            "  File: /d\n  Size: 24933642\n  Type: directory\n",
+            shell.run("stat /d").unwrap()
+        );
+        assert_eq!(
+            "/a (94853)\n├── e/ (584)\n│   └── i (584)\n├── f (29116)\n├── g (2557)\n└── h.lst (62596)\n",
+            shell.run("tree a").unwrap()
+        );
+    }
+
+    #[test]
+    pub fn runs_a_session() {
+        let tree = example();
+        let mut out = vec![];
+        Shell::new(&tree)
+            .repl("cd d\nls\nstat nope\nexit\nls\n".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(
+            "/ $ /d $ 5626152 d.ext\n8033020 d.log\n4060174 j\n7214296 k\n/d $ stat: /d/nope: no such file or directory\n/d $ ",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use crate::day07::{join_path, resolve, split_path, Entry, Expr};
+
+/// Something odd in a transcript, `at` is the index of the expression.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Issue {
+    /// `cd` into a directory no `ls` has shown.
+    UnlistedCd { at: usize, path: String },
+    /// A directory listed again with different entries.
+    ConflictingLs { at: usize, dir: String },
+    /// A path that shows up both as a file and as a directory.
+    FileAndDir { at: usize, path: String },
+    /// `cd ..` while already at the root.
+    CdAboveRoot { at: usize },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::UnlistedCd { at, path } => write!(f, "{}: cd into unlisted {}", at, path),
+            Issue::ConflictingLs { at, dir } => {
+                write!(f, "{}: ls of {} differs from before", at, dir)
+            }
+            Issue::FileAndDir { at, path } => {
+                write!(f, "{}: {} is both a file and a directory", at, path)
+            }
+            Issue::CdAboveRoot { at } => write!(f, "{}: cd .. at the root", at),
+        }
+    }
+}
+
+// replays the transcript and remembers what each path was shown as
+#[derive(Default)]
+struct Replay {
+    issues: Vec<Issue>,
+    pwd: Vec<String>,
+    kinds: BTreeMap<String, Entry>,
+    listings: BTreeMap<String, BTreeMap<String, Entry>>,
+    // the ls being read, with where it started
+    listing: Option<(usize, String, BTreeMap<String, Entry>)>,
+}
+
+impl Replay {
+    fn cwd(&self) -> String {
+        format!("/{}", self.pwd.join("/"))
+    }
+
+    // forgets what is known about the path and below, and the listing around it
+    fn forget(&mut self, path: &str) -> Vec<(String, Entry)> {
+        let below = format!("{}/", path);
+        let inside = |key: &String| key == path || key.starts_with(&below);
+        let known: Vec<(String, Entry)> = self
+            .kinds
+            .iter()
+            .filter(|(key, _)| inside(key))
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .collect();
+        self.kinds.retain(|key, _| !inside(key));
+        self.listings.retain(|key, _| !inside(key));
+        if let Some((dir, _)) = split_path(path) {
+            self.listings.remove(dir);
+        }
+        known
+    }
+
+    // a command that changes the tree
+    fn change(&mut self, at: usize, expr: &Expr) {
+        let cwd = self.cwd();
+        match expr {
+            Expr::Rm(path) => {
+                self.forget(&resolve(&cwd, path));
+            }
+            Expr::Mkdir(path) | Expr::Touch(_, path) => {
+                let path = resolve(&cwd, path);
+                let name = split_path(&path).map_or("", |(_, name)| name).to_string();
+                let entry = match expr {
+                    Expr::Touch(size, _) => Entry::File(*size, name),
+                    _ => Entry::Dir(name),
+                };
+                if let Some(known) = self.kinds.get(&path) {
+                    if std::mem::discriminant(known) != std::mem::discriminant(&entry) {
+                        self.issues.push(Issue::FileAndDir { at, path });
+                        return;
+                    }
+                }
+                self.forget(&path);
+                self.kinds.insert(path, entry);
+            }
+            Expr::Mv(from, to) => {
+                let (from, mut to) = (resolve(&cwd, from), resolve(&cwd, to));
+                if let (Some(Entry::Dir(_)), Some((_, name))) =
+                    (self.kinds.get(&to), split_path(&from))
+                {
+                    to = join_path(&to, name);
+                }
+                let moved = self.forget(&from);
+                self.forget(&to);
+                for (key, entry) in moved {
+                    self.kinds
+                        .insert(format!("{}{}", to, &key[from.len()..]), entry);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_ls(&mut self) {
+        if let Some((at, dir, entries)) = self.listing.take() {
+            match self.listings.get(&dir) {
+                Some(before) if *before != entries => {
+                    self.issues.push(Issue::ConflictingLs { at, dir });
+                }
+                Some(_) => {}
+                None => {
+                    self.listings.insert(dir, entries);
+                }
+            }
+        }
+    }
+
+    fn entry(&mut self, at: usize, entry: Entry) {
+        let path = join_path(&self.cwd(), entry.name());
+        let same_kind = |a: &Entry, b: &Entry| {
+            matches!(
+                (a, b),
+                (Entry::Dir(_), Entry::Dir(_)) | (Entry::File(_, _), Entry::File(_, _))
+            )
+        };
+        match self.kinds.get(&path) {
+            Some(known) if !same_kind(known, &entry) => {
+                self.issues.push(Issue::FileAndDir { at, path });
+            }
+            Some(_) => {}
+            None => {
+                self.kinds.insert(path, entry.clone());
+            }
+        }
+        if let Some((_, _, entries)) = &mut self.listing {
+            entries.insert(entry.name().to_string(), entry);
+        }
+    }
+
+    fn cd(&mut self, at: usize, dir: &str) {
+        let mut depth = if dir.starts_with('/') {
+            0
+        } else {
+            self.pwd.len()
+        };
+        let mut named = false;
+        for part in dir.split('/') {
+            match part {
+                "" | "." => {}
+                ".." if depth == 0 => {
+                    self.issues.push(Issue::CdAboveRoot { at });
+                    break;
+                }
+                ".." => depth -= 1,
+                _ => {
+                    depth += 1;
+                    named = true;
+                }
+            }
+        }
+        let path = resolve(&self.cwd(), dir);
+        self.pwd = path[1..]
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        // going back up only reaches directories that were checked on the way down
+        if named && path != "/" {
+            match self.kinds.get(&path) {
+                Some(Entry::Dir(_)) => {}
+                Some(Entry::File(_, _)) => self.issues.push(Issue::FileAndDir { at, path }),
+                None => self.issues.push(Issue::UnlistedCd { at, path }),
+            }
+        }
+    }
+}
+
+/// Replays the transcript and reports everything that does not add up.
+pub fn validate(exprs: &[Expr]) -> Vec<Issue> {
+    let mut replay = Replay::default();
+    for (at, expr) in exprs.iter().enumerate() {
+        match expr {
+            Expr::Cd(dir) => {
+                replay.finish_ls();
+                replay.cd(at, dir);
+            }
+            Expr::Ls => {
+                replay.finish_ls();
+                let dir = replay.cwd();
+                replay.listing = Some((at, dir, BTreeMap::new()));
+            }
+            Expr::Dir(name) => replay.entry(at, Entry::Dir(name.clone())),
+            Expr::File(size, name) => replay.entry(at, Entry::File(*size, name.clone())),
+            change => {
+                replay.finish_ls();
+                replay.change(at, change);
+            }
+        }
+    }
+    replay.finish_ls();
+    replay.issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::fixtures::parse;
+    use crate::day07::parse_input;
+
+    fn issues(session: &str) -> Vec<Issue> {
+        validate(&parse(session))
+    }
+
+    #[test]
+    pub fn the_input_is_consistent() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        assert_eq!(Vec::<Issue>::new(), validate(&parse_input(input).unwrap()));
+    }
+
+    #[test]
+    pub fn reports_every_kind_of_issue() {
+        let session = "$ cd /\n$ ls\ndir a\n10 b\n$ cd ..\n$ cd x\n$ cd /\n$ ls\ndir a\n20 b\n$ cd b\n$ cd /\n$ ls\n30 a";
+        assert_eq!(
+            vec![
+                Issue::CdAboveRoot { at: 4 },
+                Issue::UnlistedCd {
+                    at: 5,
+                    path: "/x".to_string()
+                },
+                Issue::ConflictingLs {
+                    at: 7,
+                    dir: "/".to_string()
+                },
+                Issue::FileAndDir {
+                    at: 10,
+                    path: "/b".to_string()
+                },
+                Issue::FileAndDir {
+                    at: 13,
+                    path: "/a".to_string()
+                },
+                Issue::ConflictingLs {
+                    at: 12,
+                    dir: "/".to_string()
+                },
+            ],
+            issues(session)
+        );
+        assert_eq!("4: cd .. at the root", issues(session)[0].to_string());
+    }
+
+    #[test]
+    pub fn changes_are_followed() {
+        let session = "$ cd /\n$ ls\ndir a\n10 b\n$ rm b\n$ mkdir c\n$ touch 5 a/f\n$ mv a c\n$ ls\ndir c\n$ cd c/a\n$ ls\n5 f\n$ touch 1 /c/a";
+        assert_eq!(
+            vec![Issue::FileAndDir {
+                at: 13,
+                path: "/c/a".to_string()
+            }],
+            issues(session)
+        );
+    }
+
+    #[test]
+    pub fn cd_takes_paths() {
+        let session = "$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\ndir b\n$ cd /\n$ cd a/b\n$ ls\n1 f\n$ cd ..\n$ ls\ndir b\n$ cd b/../..\n$ ls\ndir a";
+        assert!(issues(session).is_empty());
+        let session = "$ cd /\n$ ls\ndir a\n$ cd a/b\n$ cd ../..\n$ cd ../..";
+        assert_eq!(
+            vec![
+                Issue::UnlistedCd {
+                    at: 3,
+                    path: "/a/b".to_string()
+                },
+                Issue::CdAboveRoot { at: 5 },
+            ],
+            issues(session)
+        );
+    }
+
+    #[test]
+    pub fn listing_twice_is_fine() {
+        assert!(issues("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n1 f\n$ cd /\n$ ls\ndir a").is_empty());
+    }
+}
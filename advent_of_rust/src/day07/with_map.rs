@@ -1,12 +1,14 @@
-
-
-use chumsky::Parser;
 use std::collections::btree_map::Iter;
 
 use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 
-use crate::day07::Expr;
+use crate::day07::query::DirQuery;
+use crate::day07::render;
+use crate::day07::{
+    check_mkdir, check_mv, check_rm, check_touch, resolve, split_path, Entry, Expr, FileTree,
+};
+use anyhow::Error;
 use std::ops::Bound;
 
 #[derive(Debug, Clone, Default)]
@@ -26,29 +28,22 @@ impl Files {
     }
 }
 
+// every directory with the files directly inside it
 #[derive(Debug, Clone, Default)]
-pub struct FileTree {
+pub struct MapTree {
     directories: BTreeMap<String, Files>,
 }
 
-impl Display for FileTree {
+impl Display for MapTree {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut out = String::new();
-        for (dir, files) in &self.directories {
-            out.extend(dir.chars());
-            out.extend("\n".chars());
-            for (name, size) in files.iter() {
-                out.extend(format!("\t\t{} - {}\n", name, size).chars());
-            }
-        }
-        write!(f, "{}", out)
+        write!(f, "{}", render::tree(self, "/"))
     }
 }
 
-impl FileTree {
+impl MapTree {
     pub fn size_of_dir(&self, name: &str) -> Option<u64> {
         Some(
-            self.ls(name)
+            self.ls_all(name)
                 .into_iter()
                 .map(|(_name, files)| files.size())
                 .sum(),
@@ -59,66 +54,152 @@ impl FileTree {
         self.directories.keys()
     }
 
-    pub fn ls<A: Display>(&self, path: A) -> Vec<(&String, &Files)> {
+    // the directory and all directories below it, `/a` does not include `/ab`
+    pub fn ls_all<A: Display>(&self, path: A) -> Vec<(&String, &Files)> {
         let path = format!("{}", path);
+        let prefix = if path == "/" {
+            path.clone()
+        } else {
+            format!("{}/", path)
+        };
         let (from, to): (Bound<String>, Bound<String>) =
             (Bound::Included(path.to_string()), Bound::Unbounded);
         self.directories
             .range((from, to))
             .take_while(|(dir, _)| dir.starts_with(&path))
+            .filter(|(dir, _)| **dir == path || dir.starts_with(&prefix))
             .collect()
     }
 
-    pub fn add_directory(&mut self, path: &Vec<String>, name: String) {
+    pub fn add_directory(&mut self, path: &[String], name: String) {
         let path = if path.is_empty() {
-            "/".to_string()
+            format!("/{}", name)
         } else {
             format!("/{}/{}", path.join("/"), name)
         };
-        if !self.directories.contains_key(&path) {
-            self.directories.insert(path, Files::new());
-        }
+        self.directories.entry(path).or_default();
     }
-    pub fn add_file(&mut self, path: &Vec<String>, name: String, size: u64) {
+    pub fn add_file(&mut self, path: &[String], name: String, size: u64) {
         let path = if path.is_empty() {
             "/".to_string()
         } else {
             format!("/{}", path.join("/"))
         };
-        let files = self.directories.entry(path).or_insert(Files::new());
+        let files = self.directories.entry(path).or_default();
         files.add_file(name, size);
     }
 
-    pub fn find_files_smaller_then(&self, _max_size: u64) -> impl Iterator<Item = u64> + '_ {
-        self.ls("/").into_iter().filter_map(|(name, _)| {
-            let size = self.size_of_dir(&name).unwrap();
-            if size <= 100000 {
-                Some(size)
-            } else {
-                None
+    /// Removes a file, or a directory with everything inside it.
+    pub fn rm(&mut self, path: &str) -> Result<(), Error> {
+        check_rm(self, path)?;
+        if self.directories.contains_key(path) {
+            let below = format!("{}/", path);
+            self.directories
+                .retain(|dir, _| dir != path && !dir.starts_with(&below));
+        } else if let Some((dir, name)) = split_path(path) {
+            if let Some(files) = self.directories.get_mut(dir) {
+                files.0.remove(name);
             }
-        })
+        }
+        Ok(())
     }
-    pub fn part1(&self) -> u64 {
-        self.find_files_smaller_then(100000).sum()
+
+    pub fn mkdir(&mut self, path: &str) -> Result<(), Error> {
+        check_mkdir(self, path)?;
+        self.directories.entry(path.to_string()).or_default();
+        Ok(())
+    }
+
+    /// Creates a file or changes the size of an existing one.
+    pub fn touch(&mut self, path: &str, size: u64) -> Result<(), Error> {
+        check_touch(self, path)?;
+        let (dir, name) = split_path(path).expect("checked above");
+        let files = self.directories.entry(dir.to_string()).or_default();
+        files.add_file(name.to_string(), size);
+        Ok(())
+    }
+
+    /// Moves into `to` if that is a directory, otherwise renames to `to`.
+    /// A file at `to` is replaced.
+    pub fn mv(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let Some(to) = check_mv(self, from, to)? else {
+            return Ok(());
+        };
+        if self.directories.contains_key(from) {
+            let below = format!("{}/", from);
+            let moved: Vec<String> = self
+                .directories
+                .keys()
+                .filter(|dir| *dir == from || dir.starts_with(&below))
+                .cloned()
+                .collect();
+            for dir in moved {
+                let files = self.directories.remove(&dir).unwrap_or_default();
+                self.directories
+                    .insert(format!("{}{}", to, &dir[from.len()..]), files);
+            }
+        } else if let Some((dir, name)) = split_path(from) {
+            let size = self
+                .directories
+                .get_mut(dir)
+                .and_then(|files| files.0.remove(name));
+            if let Some(size) = size {
+                self.touch(&to, size)?;
+            }
+        }
+        Ok(())
     }
-    pub fn part2(&self) -> u64 {
-        let space_used = self.size_of_dir("/").unwrap();
-        let space_available = 70000000 - space_used;
-        let space_needed = 30000000 - space_available;
-        self.ls("/")
+
+    pub fn find_files_smaller_then(&self, max_size: u64) -> impl Iterator<Item = u64> + '_ {
+        self.query(&DirQuery::new().max_size(max_size))
             .into_iter()
-            .map(|(a, _b)| self.size_of_dir(a).unwrap())
-            .filter(|num| *num >= space_needed)
-            .min()
-            .unwrap()
+            .map(|(_name, size)| size)
+    }
+}
+
+impl FileTree for MapTree {
+    fn dir_size(&self, dir: &str) -> u64 {
+        self.size_of_dir(dir).unwrap_or(0)
+    }
+
+    fn dirs(&self) -> Vec<String> {
+        self.directories().cloned().collect()
+    }
+
+    fn ls(&self, dir: &str) -> Vec<Entry> {
+        let prefix = if dir == "/" {
+            "/".to_string()
+        } else {
+            format!("{}/", dir)
+        };
+        let mut entries: Vec<Entry> = self
+            .directories
+            .keys()
+            .filter_map(|path| path.strip_prefix(&prefix))
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .map(|name| Entry::Dir(name.to_string()))
+            .collect();
+        if let Some(files) = self.directories.get(dir) {
+            entries.extend(
+                files
+                    .iter()
+                    .map(|(name, size)| Entry::File(*size, name.clone())),
+            );
+        }
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        entries
     }
 }
 
-pub fn create_file_tree(expressions: Vec<Expr>) -> FileTree {
-    let mut file_tree = FileTree {
+fn pwd(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+pub fn create_file_tree(expressions: Vec<Expr>) -> MapTree {
+    let mut file_tree = MapTree {
         ..Default::default()
     };
+    file_tree.directories.insert("/".to_string(), Files::new());
     let mut current_path = vec![];
     for expression in expressions {
         match expression {
@@ -126,13 +207,17 @@ pub fn create_file_tree(expressions: Vec<Expr>) -> FileTree {
                 current_path.clear();
             }
             Expr::Cd(dir) if dir == ".." => {
-                if current_path.len() < 1 {
+                if current_path.is_empty() {
                     panic!("Invalid input")
                 }
                 current_path.pop();
             }
             Expr::Cd(dir) => {
-                current_path.push(dir);
+                current_path = resolve(&pwd(&current_path), &dir)
+                    .split('/')
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect();
             }
             Expr::Ls => {
                 // nothing to do ;)
@@ -143,6 +228,20 @@ pub fn create_file_tree(expressions: Vec<Expr>) -> FileTree {
             Expr::File(size, name) => {
                 file_tree.add_file(&current_path, name, size);
             }
+            // changes that fail are skipped, like with the other trees
+            Expr::Rm(path) => {
+                let _ = file_tree.rm(&resolve(&pwd(&current_path), &path));
+            }
+            Expr::Mkdir(path) => {
+                let _ = file_tree.mkdir(&resolve(&pwd(&current_path), &path));
+            }
+            Expr::Touch(size, path) => {
+                let _ = file_tree.touch(&resolve(&pwd(&current_path), &path), size);
+            }
+            Expr::Mv(from, to) => {
+                let cwd = pwd(&current_path);
+                let _ = file_tree.mv(&resolve(&cwd, &from), &resolve(&cwd, &to));
+            }
         }
     }
     file_tree
@@ -0,0 +1,564 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Error};
+
+use crate::day07::{join_path, resolve, Entry, Expr, FileTree};
+
+/// Index of a node in a `NodeTree`, the root is `0`.
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Kind {
+    File,
+    Dir(BTreeMap<String, NodeId>),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<NodeId>,
+    pub kind: Kind,
+    /// The size of a file, or of everything below a directory.
+    pub size: u64,
+}
+
+// all nodes in one vector, removed nodes stay in it without a parent
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NodeTree {
+    nodes: Vec<Node>,
+    // whether nodes were added since the sizes were computed
+    stale: bool,
+}
+
+impl Default for NodeTree {
+    fn default() -> Self {
+        NodeTree {
+            nodes: vec![Node {
+                name: "/".to_string(),
+                parent: None,
+                kind: Kind::Dir(BTreeMap::new()),
+                size: 0,
+            }],
+            stale: false,
+        }
+    }
+}
+
+impl NodeTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// The number of nodes below the root and the root itself, removed nodes do not count.
+    pub fn len(&self) -> usize {
+        let mut count = 0;
+        let mut todo = vec![0];
+        while let Some(id) = todo.pop() {
+            count += 1;
+            if let Kind::Dir(children) = &self.nodes[id].kind {
+                todo.extend(children.values());
+            }
+        }
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(&self.nodes[0].kind, Kind::Dir(children) if children.is_empty())
+    }
+
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        match &self.nodes[dir].kind {
+            Kind::Dir(children) => children.get(name).cloned(),
+            Kind::File => None,
+        }
+    }
+
+    /// The node at an absolute path like `/a/e`.
+    pub fn find(&self, path: &str) -> Option<NodeId> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(0, |dir, name| self.child(dir, name))
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = vec![];
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            names.push(self.nodes[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Adds a directory, or returns the one that is already there.
+    /// Fails when `dir` or the existing entry is a file.
+    pub fn add_dir(&mut self, dir: NodeId, name: &str) -> Result<NodeId, Error> {
+        self.add(dir, name, Kind::Dir(BTreeMap::new()), 0)
+    }
+
+    /// Adds a file, or returns the one that is already there. Sizes are
+    /// only correct again after `compute_sizes`, unlike with `touch`.
+    /// Fails when `dir` or the existing entry is a directory.
+    pub fn add_file(&mut self, dir: NodeId, name: &str, size: u64) -> Result<NodeId, Error> {
+        self.add(dir, name, Kind::File, size)
+    }
+
+    fn add(&mut self, dir: NodeId, name: &str, kind: Kind, size: u64) -> Result<NodeId, Error> {
+        if let Some(id) = self.child(dir, name) {
+            return match (&self.nodes[id].kind, kind) {
+                (Kind::File, Kind::File) | (Kind::Dir(_), Kind::Dir(_)) => Ok(id),
+                (Kind::File, _) => Err(anyhow!("{}: is a file", self.path(id))),
+                (Kind::Dir(_), _) => Err(anyhow!("{}: is a directory", self.path(id))),
+            };
+        }
+        let id = self.nodes.len();
+        match &mut self.nodes[dir].kind {
+            Kind::Dir(children) => children.insert(name.to_string(), id),
+            Kind::File => return Err(anyhow!("{}: not a directory", self.path(dir))),
+        };
+        self.stale = true;
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(dir),
+            kind,
+            size,
+        });
+        Ok(id)
+    }
+
+    /// Sums up the sizes of all directories in one pass. Going backwards
+    /// through the depth first order visits every node before its parent.
+    pub fn compute_sizes(&mut self) {
+        self.stale = false;
+        let mut order = vec![];
+        let mut todo = vec![0];
+        while let Some(id) = todo.pop() {
+            order.push(id);
+            if let Kind::Dir(children) = &mut self.nodes[id].kind {
+                todo.extend(children.values());
+                self.nodes[id].size = 0;
+            }
+        }
+        for id in order.into_iter().rev() {
+            let size = self.nodes[id].size;
+            if let Some(parent) = self.nodes[id].parent {
+                self.nodes[parent].size += size;
+            }
+        }
+    }
+
+    // the mutations below keep the sizes up to date from here on
+    fn fresh_sizes(&mut self) {
+        if self.stale {
+            self.compute_sizes();
+        }
+    }
+
+    fn grow(&mut self, dir: NodeId, size: u64) {
+        let mut current = Some(dir);
+        while let Some(id) = current {
+            self.nodes[id].size += size;
+            current = self.nodes[id].parent;
+        }
+    }
+
+    fn shrink(&mut self, dir: NodeId, size: u64) {
+        let mut current = Some(dir);
+        while let Some(id) = current {
+            self.nodes[id].size -= size;
+            current = self.nodes[id].parent;
+        }
+    }
+
+    // the directory an absolute path goes into, and the last name of the path
+    fn split<'p>(&self, path: &'p str) -> Result<(NodeId, &'p str), Error> {
+        let (parent, name) = path
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .filter(|(_, name)| !name.is_empty())
+            .ok_or_else(|| anyhow!("{}: not a path below /", path))?;
+        match self.find(parent) {
+            Some(id) if matches!(self.nodes[id].kind, Kind::Dir(_)) => Ok((id, name)),
+            _ => Err(anyhow!("{}: no such directory", parent)),
+        }
+    }
+
+    // takes the node out of its directory, it is no longer part of the tree
+    fn detach(&mut self, id: NodeId) {
+        if let Some(parent) = self.nodes[id].parent.take() {
+            let name = self.nodes[id].name.clone();
+            if let Kind::Dir(children) = &mut self.nodes[parent].kind {
+                children.remove(&name);
+            }
+            let size = self.nodes[id].size;
+            self.shrink(parent, size);
+        }
+    }
+
+    /// Removes a file, or a directory with everything inside it.
+    pub fn rm(&mut self, path: &str) -> Result<(), Error> {
+        self.fresh_sizes();
+        match self.find(path) {
+            Some(0) => Err(anyhow!("rm: can not remove /")),
+            Some(id) => {
+                self.detach(id);
+                Ok(())
+            }
+            None => Err(anyhow!("rm: {}: no such file or directory", path)),
+        }
+    }
+
+    pub fn mkdir(&mut self, path: &str) -> Result<NodeId, Error> {
+        self.fresh_sizes();
+        let (dir, name) = self.split(path)?;
+        if self.child(dir, name).is_some() {
+            return Err(anyhow!("mkdir: {}: already exists", path));
+        }
+        let id = self.add_dir(dir, name)?;
+        self.stale = false;
+        Ok(id)
+    }
+
+    /// Creates a file or changes the size of an existing one.
+    pub fn touch(&mut self, path: &str, size: u64) -> Result<NodeId, Error> {
+        self.fresh_sizes();
+        let (dir, name) = self.split(path)?;
+        let id = match self.child(dir, name) {
+            Some(id) if self.nodes[id].kind != Kind::File => {
+                return Err(anyhow!("touch: {}: is a directory", path))
+            }
+            Some(id) => {
+                let old = self.nodes[id].size;
+                self.shrink(dir, old);
+                self.nodes[id].size = size;
+                id
+            }
+            None => self.add_file(dir, name, size)?,
+        };
+        self.grow(dir, size);
+        self.stale = false;
+        Ok(id)
+    }
+
+    /// Moves into `to` if that is a directory, otherwise renames to `to`.
+    /// A file at `to` is replaced.
+    pub fn mv(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        self.fresh_sizes();
+        let id = match self.find(from) {
+            Some(0) => return Err(anyhow!("mv: can not move /")),
+            Some(id) => id,
+            None => return Err(anyhow!("mv: {}: no such file or directory", from)),
+        };
+        let (dir, name) = match self.find(to) {
+            Some(dir) if matches!(self.nodes[dir].kind, Kind::Dir(_)) => {
+                (dir, self.nodes[id].name.clone())
+            }
+            _ => {
+                let (dir, name) = self.split(to)?;
+                (dir, name.to_string())
+            }
+        };
+        let mut current = Some(dir);
+        while let Some(ancestor) = current {
+            if ancestor == id {
+                return Err(anyhow!("mv: can not move {} into itself", from));
+            }
+            current = self.nodes[ancestor].parent;
+        }
+        match self.child(dir, &name) {
+            Some(other) if other == id => return Ok(()),
+            Some(other) if self.nodes[other].kind == Kind::File => {
+                if self.nodes[id].kind != Kind::File {
+                    return Err(anyhow!("mv: can not replace a file with a directory"));
+                }
+                self.detach(other);
+            }
+            Some(_) => return Err(anyhow!("mv: {}/{}: already exists", to, name)),
+            None => {}
+        }
+        self.detach(id);
+        if let Kind::Dir(children) = &mut self.nodes[dir].kind {
+            children.insert(name.clone(), id);
+        }
+        self.nodes[id].name = name;
+        self.nodes[id].parent = Some(dir);
+        let size = self.nodes[id].size;
+        self.grow(dir, size);
+        Ok(())
+    }
+
+    // applies one expression with `pwd` as the working directory, returns the new one
+    fn apply(&mut self, pwd: NodeId, expr: Expr) -> Result<NodeId, Error> {
+        let path = |path: &str| resolve(&self.path(pwd), path);
+        match expr {
+            // directories that were not listed yet are created on the way
+            Expr::Cd(dir) => {
+                let target = path(&dir);
+                return target
+                    .split('/')
+                    .filter(|name| !name.is_empty())
+                    .try_fold(0, |id, name| self.add_dir(id, name));
+            }
+            Expr::Ls => {}
+            Expr::Dir(name) => {
+                self.add_dir(pwd, &name)?;
+            }
+            Expr::File(size, name) => {
+                let id = self.add_file(pwd, &name, size)?;
+                let old = self.nodes[id].size;
+                // a file listed again may have changed, like with `touch`
+                if !self.stale {
+                    self.shrink(pwd, old);
+                    self.grow(pwd, size);
+                }
+                self.nodes[id].size = size;
+            }
+            Expr::Rm(target) => self.rm(&path(&target))?,
+            Expr::Mkdir(target) => {
+                self.mkdir(&path(&target))?;
+            }
+            Expr::Touch(size, target) => {
+                self.touch(&path(&target), size)?;
+            }
+            Expr::Mv(from, to) => {
+                let (from, to) = (path(&from), path(&to));
+                self.mv(&from, &to)?
+            }
+        }
+        Ok(pwd)
+    }
+
+    /// Builds the tree like `from`, but stops at the first command that fails.
+    pub fn replay(exprs: Vec<Expr>) -> Result<Self, Error> {
+        let mut tree = NodeTree::new();
+        let mut pwd = 0;
+        for (idx, expr) in exprs.into_iter().enumerate() {
+            let line = expr.to_string();
+            pwd = tree
+                .apply(pwd, expr)
+                .map_err(|err| anyhow!("{}: {}: {}", idx, line, err))?;
+        }
+        tree.compute_sizes();
+        Ok(tree)
+    }
+}
+
+impl FileTree for NodeTree {
+    fn dir_size(&self, dir: &str) -> u64 {
+        self.find(dir).map_or(0, |id| self.nodes[id].size)
+    }
+
+    fn dirs(&self) -> Vec<String> {
+        self.dirs_sized().into_iter().map(|(dir, _)| dir).collect()
+    }
+
+    fn ls(&self, dir: &str) -> Vec<Entry> {
+        match self.find(dir).map(|id| &self.nodes[id].kind) {
+            Some(Kind::Dir(children)) => children
+                .iter()
+                .map(|(name, id)| match self.nodes[*id].kind {
+                    Kind::Dir(_) => Entry::Dir(name.clone()),
+                    Kind::File => Entry::File(self.nodes[*id].size, name.clone()),
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // walks the tree once, without recursion so deep trees are fine
+    fn dirs_sized(&self) -> Vec<(String, u64)> {
+        let mut dirs = vec![];
+        let mut stack = vec![(0, "/".to_string())];
+        while let Some((id, path)) = stack.pop() {
+            if let Kind::Dir(children) = &self.nodes[id].kind {
+                for (name, child) in children.iter().rev() {
+                    if let Kind::Dir(_) = self.nodes[*child].kind {
+                        stack.push((*child, join_path(&path, name)));
+                    }
+                }
+            }
+            dirs.push((path, self.nodes[id].size));
+        }
+        dirs
+    }
+}
+
+// commands that fail are skipped
+impl From<Vec<Expr>> for NodeTree {
+    fn from(exprs: Vec<Expr>) -> Self {
+        let mut tree = NodeTree::new();
+        let mut pwd = 0;
+        for expr in exprs {
+            pwd = tree.apply(pwd, expr).unwrap_or(pwd);
+        }
+        tree.compute_sizes();
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day07::fixtures::{lines, parse};
+    use crate::day07::Day07;
+
+    #[test]
+    pub fn sizes_deeply_nested_trees() {
+        // 1000 directories inside each other, with 200 files each
+        let mut exprs = vec![Expr::Cd("/".to_string())];
+        for depth in 0..1000 {
+            exprs.push(Expr::Ls);
+            exprs.push(Expr::Dir("d".to_string()));
+            for file in 0..200 {
+                exprs.push(Expr::File(1, format!("f{}", file)));
+            }
+            exprs.push(Expr::Cd("d".to_string()));
+            if depth % 100 == 0 {
+                exprs.push(Expr::Cd("..".to_string()));
+                exprs.push(Expr::Cd("d".to_string()));
+            }
+        }
+        let tree: NodeTree = exprs.into();
+        assert_eq!(1 + 1000 * 201, tree.len());
+
+        let dirs = tree.dirs_sized();
+        assert_eq!(1001, dirs.len());
+        assert_eq!(("/".to_string(), 200000), dirs[0]);
+        assert_eq!(("/d".to_string(), 199800), dirs[1]);
+        assert_eq!(0, dirs[1000].1);
+        assert_eq!(199800, tree.dir_size("/d"));
+        assert_eq!(200, tree.dir_size(&dirs[999].0));
+        // everything from 500 directories down is at most 100000
+        let sum: u64 = (0..=500).map(|d| d * 200).sum();
+        assert_eq!(format!("Sum of dirs is {}", sum), tree.part_one());
+    }
+
+    #[test]
+    pub fn changes_keep_sizes_up_to_date() {
+        let mut tree = NodeTree::new();
+        let a = tree.add_dir(0, "a").unwrap();
+        tree.add_file(a, "f", 100).unwrap();
+        tree.add_file(0, "g", 10).unwrap();
+        tree.touch("/a/h", 5).unwrap();
+        assert_eq!(115, tree.dir_size("/"));
+        assert_eq!(105, tree.dir_size("/a"));
+
+        tree.mkdir("/b").unwrap();
+        tree.mv("/a/f", "/b").unwrap();
+        assert_eq!((5, 100), (tree.dir_size("/a"), tree.dir_size("/b")));
+        tree.mv("/b", "/a/c").unwrap();
+        assert_eq!(105, tree.dir_size("/a"));
+        assert_eq!(Some(100), tree.find("/a/c/f").map(|id| tree.node(id).size));
+        tree.touch("/a/c/f", 1).unwrap();
+        assert_eq!((6, 16), (tree.dir_size("/a"), tree.dir_size("/")));
+        tree.rm("/a/c").unwrap();
+        assert_eq!((5, 15), (tree.dir_size("/a"), tree.dir_size("/")));
+        // everything still adds up when computed from scratch
+        let sizes = tree.dirs_sized();
+        tree.compute_sizes();
+        assert_eq!(sizes, tree.dirs_sized());
+
+        assert!(tree.rm("/").is_err());
+        assert!(tree.rm("/nope").is_err());
+        assert!(tree.mkdir("/a").is_err());
+        assert!(tree.mkdir("/x/y").is_err());
+        assert!(tree.touch("/a", 1).is_err());
+        assert!(tree.mv("/a", "/a/h").is_err());
+        tree.mkdir("/a/d").unwrap();
+        assert!(tree.mv("/a", "/a/d").is_err());
+    }
+
+    #[test]
+    pub fn replays_what_if_scenarios() {
+        let input = crate::get_input("../inputs/day07.txt").unwrap();
+        let mut exprs = crate::day07::parse_input(input).unwrap();
+        let tree: NodeTree = exprs.clone().into();
+        let (dir, size) = crate::day07::disk::Disk::default()
+            .smallest_dir_to_delete(&tree)
+            .unwrap()
+            .unwrap();
+        exprs.push(Expr::Rm(dir));
+        let after = NodeTree::replay(exprs.clone()).unwrap();
+        assert_eq!(tree.dir_size("/") - size, after.dir_size("/"));
+
+        exprs.push(Expr::Rm("/nope".to_string()));
+        assert!(NodeTree::replay(exprs).is_err());
+    }
+
+    #[test]
+    pub fn paths_and_lookups() {
+        let mut tree = NodeTree::new();
+        let a = tree.add_dir(0, "a").unwrap();
+        let e = tree.add_dir(a, "e").unwrap();
+        tree.add_file(e, "i", 584).unwrap();
+        tree.add_file(0, "b.txt", 100).unwrap();
+        assert_eq!(a, tree.add_dir(0, "a").unwrap());
+        assert!(tree.add_file(0, "a", 1).is_err());
+        assert!(tree.add_dir(0, "b.txt").is_err());
+        assert!(tree.add_file(tree.find("/b.txt").unwrap(), "x", 1).is_err());
+        tree.compute_sizes();
+        assert_eq!("/a/e", tree.path(e));
+        assert_eq!("/", tree.path(0));
+        assert_eq!(Some(e), tree.find("/a/e"));
+        assert_eq!(None, tree.find("/a/x"));
+        assert_eq!(684, tree.dir_size("/"));
+        assert_eq!(
+            vec![
+                ("/".to_string(), 684),
+                ("/a".to_string(), 584),
+                ("/a/e".to_string(), 584)
+            ],
+            tree.dirs_sized()
+        );
+        assert_eq!(5, tree.len());
+        tree.rm("/a").unwrap();
+        assert_eq!(2, tree.len());
+        tree.rm("/b.txt").unwrap();
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    pub fn files_and_directories_do_not_mix() {
+        let input = "$ cd /\n$ ls\n10 a\n$ cd a\n$ ls\n5 b";
+        assert_eq!(
+            "3: $ cd a: /a: is a file",
+            crate::day07::part1(lines(input)).unwrap_err().to_string()
+        );
+        assert!(NodeTree::replay(parse(input)).is_err());
+        assert!(NodeTree::replay(parse("$ cd /\n$ ls\n10 a\n$ ls\ndir a")).is_err());
+        assert!(NodeTree::replay(parse("$ cd /\n$ ls\ndir a\n$ ls\n10 a")).is_err());
+
+        let tree =
+            NodeTree::replay(parse("$ cd /\n$ cd c/a\n$ ls\n5 b\n$ cd ../..\n$ ls\n1 d")).unwrap();
+        assert_eq!(Some(5), tree.find("/c/a/b").map(|id| tree.node(id).size));
+        assert_eq!(vec![Entry::Dir("a".to_string())], tree.ls("/c"));
+        assert_eq!(6, tree.dir_size("/"));
+    }
+
+    #[test]
+    pub fn listing_again_after_changes() {
+        let input = "$ cd /\n$ ls\n10 a\ndir d\n$ touch 1 x\n$ ls\n20 a\n$ rm a";
+        assert_eq!(
+            "Sum of dirs is 1",
+            crate::day07::part1(lines(input)).unwrap()
+        );
+        let exprs = crate::day07::parse_input(lines(input)).unwrap();
+        let tree = NodeTree::replay(exprs[..7].to_vec()).unwrap();
+        assert_eq!(21, tree.dir_size("/"));
+        let mut tree = NodeTree::replay(exprs).unwrap();
+        assert_eq!(1, tree.dir_size("/"));
+        // a directory listed as a file is an error
+        let input = "$ cd /\n$ ls\n10 a\ndir d\n$ touch 1 x\n$ ls\n50 d\n$ rm d";
+        assert!(crate::day07::part1(lines(input)).is_err());
+        // the cached sizes match sizes computed from scratch
+        tree.touch("/d/y", 4).unwrap();
+        let sizes = tree.dirs_sized();
+        tree.compute_sizes();
+        assert_eq!(sizes, tree.dirs_sized());
+    }
+}
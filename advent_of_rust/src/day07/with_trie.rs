@@ -1,170 +1,238 @@
-use crate::day07::{Day07, Expr};
+use std::fmt::{Display, Formatter};
+
+use crate::day07::render;
+use crate::day07::{
+    check_mkdir, check_mv, check_rm, check_touch, join_path, resolve, Entry, Expr, FileTree,
+};
+
+use anyhow::Error;
 
 use radix_trie::{Trie, TrieCommon};
 
-// we are using a trie for fast look up.
-pub struct FileTree(Trie<String, u64>);
+// what is stored under a path, a file may well be empty
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Item {
+    Dir,
+    File(u64),
+}
 
-impl FileTree {
-    // adds the size of all files and directories below the given dir
-    pub fn dir_size<A: AsRef<str>>(&self, dir: A) -> u64 {
-        let dir = dir.as_ref().to_string();
-        match self.0.subtrie(&dir) {
-            None => 0,
-            Some(sub) => sub.iter().fold(0, |acc, c| acc + *c.1),
+impl Item {
+    fn size(&self) -> u64 {
+        match self {
+            Item::Dir => 0,
+            Item::File(size) => *size,
         }
     }
-    // get all directories!
-    pub fn dirs(&self) -> impl Iterator<Item = &String> + '_ {
-        self.0
-            .iter()
-            .filter_map(|(k, v)| if *v == 0 { Some(k) } else { None })
-    }
+}
+
+// we are using a trie for fast look up.
+pub struct TrieTree(Trie<String, Item>);
 
-    pub fn dirs_sized(&self) -> impl Iterator<Item = (&String, u64)> + '_ {
-        self.dirs().map(|dir| (dir, self.dir_size(dir)))
+impl Display for TrieTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", render::tree(self, "/"))
     }
+}
 
+impl TrieTree {
     pub fn debugs(&self) {
         for (a, b) in self.0.iter() {
-            println!("{} == {}", a, b);
+            println!("{} == {}", a, b.size());
         }
     }
+
+    // everything inside the directory, the directory itself excluded
+    // the trailing slash keeps `/a/b.txt` out of the directory `/a/b`
+    fn below<'a>(&'a self, dir: &str) -> impl Iterator<Item = (&'a str, Item)> + 'a {
+        let prefix = if dir == "/" {
+            "/".to_string()
+        } else {
+            format!("{}/", dir)
+        };
+        self.0
+            .get_raw_descendant(&prefix)
+            .into_iter()
+            .flat_map(|sub| sub.iter())
+            .filter_map(move |(path, size)| {
+                let name = path.strip_prefix(&prefix)?;
+                if name.is_empty() {
+                    None
+                } else {
+                    Some((name, *size))
+                }
+            })
+    }
 }
 
-impl Day07 for FileTree {
-    fn part_one(&self) -> String {
-        let sum: u64 = self
-            .dirs_sized()
-            .filter_map(
-                |(_name, size)| {
-                    if size <= 100000 {
-                        Some(size)
-                    } else {
-                        None
-                    }
-                },
-            )
-            .sum();
-        format!("Sum of dirs is {}", sum)
+impl TrieTree {
+    // the path itself and everything below it, with full paths
+    fn subtree(&self, path: &str) -> Vec<(String, Item)> {
+        let mut items: Vec<(String, Item)> = self
+            .below(path)
+            .map(|(name, item)| (join_path(path, name), item))
+            .collect();
+        if let Some(item) = self.0.get(path) {
+            items.push((path.to_string(), *item));
+        }
+        items
     }
 
-    fn part_two(&self) -> String {
-        let space_used = self.dir_size("/");
-        let space_available = 70000000 - space_used;
-        let space_needed = 30000000 - space_available;
-        let minimum_size = self
-            .dirs_sized()
-            .filter_map(|(_name, num)| {
-                if num >= space_needed {
-                    Some(num.clone())
+    /// Removes a file, or a directory with everything inside it.
+    pub fn rm(&mut self, path: &str) -> Result<(), Error> {
+        check_rm(self, path)?;
+        for (key, _) in self.subtree(path) {
+            self.0.remove(&key);
+        }
+        Ok(())
+    }
+
+    pub fn mkdir(&mut self, path: &str) -> Result<(), Error> {
+        check_mkdir(self, path)?;
+        self.0.insert(path.to_string(), Item::Dir);
+        Ok(())
+    }
+
+    /// Creates a file or changes the size of an existing one.
+    pub fn touch(&mut self, path: &str, size: u64) -> Result<(), Error> {
+        check_touch(self, path)?;
+        self.0.insert(path.to_string(), Item::File(size));
+        Ok(())
+    }
+
+    /// Moves into `to` if that is a directory, otherwise renames to `to`.
+    /// A file at `to` is replaced.
+    pub fn mv(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let Some(to) = check_mv(self, from, to)? else {
+            return Ok(());
+        };
+        let moved = self.subtree(from);
+        for (key, _) in &moved {
+            self.0.remove(key);
+        }
+        for (key, item) in moved {
+            self.0.insert(format!("{}{}", to, &key[from.len()..]), item);
+        }
+        Ok(())
+    }
+}
+
+impl FileTree for TrieTree {
+    // adds the size of all files and directories below the given dir
+    fn dir_size(&self, dir: &str) -> u64 {
+        self.below(dir).fold(0, |acc, (_, item)| acc + item.size())
+    }
+    // get all directories!
+    fn dirs(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .filter_map(|(k, v)| {
+                if *v == Item::Dir {
+                    Some(k.clone())
                 } else {
                     None
                 }
             })
-            .min()
-            .unwrap();
-        format!("Min sufficient size is: {}", minimum_size)
+            .collect()
+    }
+
+    fn ls(&self, dir: &str) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .below(dir)
+            .filter(|(name, _)| !name.contains('/'))
+            .map(|(name, item)| match item {
+                Item::Dir => Entry::Dir(name.to_string()),
+                Item::File(size) => Entry::File(size, name.to_string()),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        entries
     }
 }
 
-impl From<Vec<Expr>> for FileTree {
+impl From<Vec<Expr>> for TrieTree {
     fn from(s: Vec<Expr>) -> Self {
         from_expressions(s)
     }
 }
 
-fn from_expressions(exprs: Vec<Expr>) -> FileTree {
-    let mut trie: Trie<String, u64> = radix_trie::Trie::new();
+fn from_expressions(exprs: Vec<Expr>) -> TrieTree {
+    let mut trie: Trie<String, Item> = radix_trie::Trie::new();
     let pwd: Vec<String> = vec![];
-    fn pwd_to_str(pwd: &Vec<String>) -> String {
+    fn pwd_to_str(pwd: &[String]) -> String {
         if pwd.is_empty() {
             "".to_string()
         } else {
             format!("/{}", pwd.join("/"))
         }
     }
-    trie.insert("/".to_string(), 0);
-    FileTree(
-        exprs
-            .into_iter()
-            .fold((trie, pwd), |(mut trie, mut pwd), expr| {
-                match expr {
-                    Expr::Cd(dir) if dir == ".." => (trie, {
-                        pwd.pop();
-                        pwd
-                    }),
-                    Expr::Cd(dir) if dir == "/" => (trie, {
-                        pwd.clear();
-                        pwd
-                    }),
-                    Expr::Cd(dir) => (trie, {
-                        pwd.push(dir);
-                        pwd
-                    }),
-                    Expr::Ls => (trie, pwd),
-                    Expr::Dir(directory) => {
-                        let key = format!("{}/{}", pwd_to_str(&pwd), directory); // this is silly!?
-                        trie.insert(key, 0); // needed for subDirs!?
-                        (trie, pwd) // really !? or zero!?
-                    }
-                    Expr::File(size, name) => {
-                        let key = format!("{}/{}", pwd_to_str(&pwd), name);
-                        trie.insert(key, size);
-                        (trie, pwd)
-                    }
+    trie.insert("/".to_string(), Item::Dir);
+    exprs
+        .into_iter()
+        .fold((TrieTree(trie), pwd), |(mut trie, pwd), expr| {
+            match expr {
+                Expr::Cd(dir) => {
+                    let pwd = resolve(&pwd_to_str(&pwd), &dir)
+                        .split('/')
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    (trie, pwd)
                 }
-            })
-            .0,
-    )
+                Expr::Ls => (trie, pwd),
+                Expr::Dir(directory) => {
+                    let key = format!("{}/{}", pwd_to_str(&pwd), directory); // this is silly!?
+                    trie.0.insert(key, Item::Dir);
+                    (trie, pwd)
+                }
+                Expr::File(size, name) => {
+                    let key = format!("{}/{}", pwd_to_str(&pwd), name);
+                    trie.0.insert(key, Item::File(size));
+                    (trie, pwd)
+                }
+                // changes that fail are skipped, like with the other trees
+                Expr::Rm(path) => {
+                    let _ = trie.rm(&resolve(&pwd_to_str(&pwd), &path));
+                    (trie, pwd)
+                }
+                Expr::Mkdir(path) => {
+                    let _ = trie.mkdir(&resolve(&pwd_to_str(&pwd), &path));
+                    (trie, pwd)
+                }
+                Expr::Touch(size, path) => {
+                    let _ = trie.touch(&resolve(&pwd_to_str(&pwd), &path), size);
+                    (trie, pwd)
+                }
+                Expr::Mv(from, to) => {
+                    let cwd = pwd_to_str(&pwd);
+                    let _ = trie.mv(&resolve(&cwd, &from), &resolve(&cwd, &to));
+                    (trie, pwd)
+                }
+            }
+        })
+        .0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::day07::parse_input;
-
-    const TEST_INPUT: &'static str = r###"$ cd /
-$ ls
-dir a
-14848514 b.txt
-8504156 c.dat
-dir d
-$ cd a
-$ ls
-dir e
-29116 f
-2557 g
-62596 h.lst
-$ cd e
-$ ls
-584 i
-$ cd ..
-$ cd ..
-$ cd d
-$ ls
-4060174 j
-8033020 d.log
-5626152 d.ext
-7214296 k"###;
+    use crate::day07::fixtures::example_exprs;
 
     #[test]
     pub fn tries_try() {
-        let res: Vec<Expr> =
-            parse_input(TEST_INPUT.split("\n").map(|c| c.to_string()).collect()).unwrap();
-        let file_tree: FileTree = res.into();
+        let file_tree: TrieTree = example_exprs().into();
 
-        let size = file_tree.dir_size("/");
         file_tree.debugs();
         file_tree
             .dirs()
-            .map(|dir| (dir.clone(), file_tree.dir_size(dir)))
+            .into_iter()
+            .map(|dir| (dir.clone(), file_tree.dir_size(&dir)))
             .for_each(|(dir, size)| println!("{} :: {}", dir, size));
 
         let part_one: u64 = file_tree
             .dirs_sized()
+            .into_iter()
             .filter_map(
-                |(name, size)| {
+                |(_name, size)| {
                     if size <= 100000 {
                         Some(size)
                     } else {
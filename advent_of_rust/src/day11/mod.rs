@@ -1,30 +1,80 @@
-use std::collections::BTreeMap;
-use std::process::id;
-use chumsky::chain::Chain;
-use chumsky::prelude::todo;
 use iter_tools::Itertools;
-use lazy_static::lazy_static;
-use pest::error::Error;
 use pest::iterators::Pairs;
 use pest::Parser;
+use std::collections::BTreeMap;
 
 pub fn part1(input: Vec<String>) -> Result<String, anyhow::Error> {
     let monkeys = parse_monkeys(&input.join("\n"));
-    let mut monkeys : BTreeMap<u32, Monkey> = monkeys.into_iter().map(|m| (m.id, m)).collect();
-    let mut rounds = 0;
-    let monkeys_after_20_rounds = (0..20).fold(monkeys, |monkeys, round|{
-        rounds += 1;
-        do_monkey_dance(monkeys)
-    });
-    let mut most_active_monkeys = monkeys_after_20_rounds.values()
-        .sorted_by(|a,b|  b.number_of_inspected_items.cmp(&a.number_of_inspected_items));
+    let monkey_business = monkey_business(monkeys, 20, &Relief::DivideBy(3));
+    Ok(format!(
+        "Monkey business after {} rounds is {}",
+        20, monkey_business
+    ))
+}
+
+pub fn part2(input: Vec<String>) -> Result<String, anyhow::Error> {
+    let monkeys = parse_monkeys(&input.join("\n"));
+    let relief = Relief::modulo(&monkeys);
+    let monkey_business = monkey_business(monkeys, 10000, &relief);
+    Ok(format!(
+        "Monkey business after {} rounds is {}",
+        10000, monkey_business
+    ))
+}
+
+/// The least common multiple of the divisors every monkey tests with.
+pub fn common_multiple(monkeys: &[Monkey]) -> i64 {
+    fn gcd(a: i64, b: i64) -> i64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    monkeys.iter().fold(1, |lcm, monkey| {
+        let Test::DivisibleBy(value) = monkey.test;
+        lcm / gcd(lcm, value) * value
+    })
+}
 
-    let m1 = most_active_monkeys.next().unwrap();
-    let m2 = most_active_monkeys.next().unwrap();
-    println!("{}, {}", m1.number_of_inspected_items, m2.number_of_inspected_items);
-    let monkey_business = m1.number_of_inspected_items * m2.number_of_inspected_items;
+/// What happens to a worry level after a monkey inspected an item.
+pub enum Relief {
+    /// The monkey gets bored, part one divides by 3.
+    DivideBy(i64),
+    /// Worry levels only grow, this overflows after a few rounds.
+    None,
+    /// Keeps worry levels small without changing any throw.
+    Modulo(i64),
+    Custom(Box<dyn Fn(i64) -> i64>),
+}
+
+impl Relief {
+    // the tests only care about divisibility, so worry levels can be kept
+    // modulo a common multiple of all divisors
+    pub fn modulo(monkeys: &[Monkey]) -> Relief {
+        Relief::Modulo(common_multiple(monkeys))
+    }
 
-    Ok(format!("Monkey business after {} rounds is {}", rounds,monkey_business))
+    pub fn apply(&self, level: i64) -> i64 {
+        match self {
+            Relief::DivideBy(value) => level / value,
+            Relief::None => level,
+            Relief::Modulo(value) => level % value,
+            Relief::Custom(relief) => relief(level),
+        }
+    }
+}
+
+/// Inspections of the two most active monkeys multiplied, after the given number of rounds.
+pub fn monkey_business(monkeys: Vec<Monkey>, rounds: usize, relief: &Relief) -> usize {
+    let monkeys: BTreeMap<u32, Monkey> = monkeys.into_iter().map(|m| (m.id, m)).collect();
+    let monkeys = (0..rounds).fold(monkeys, |monkeys, _| do_monkey_dance(monkeys, relief));
+    monkeys
+        .values()
+        .map(|monkey| monkey.number_of_inspected_items)
+        .sorted_by(|a, b| b.cmp(a))
+        .take(2)
+        .product()
 }
 
 #[derive(pest_derive::Parser)]
@@ -37,39 +87,29 @@ pub enum Operation {
     Subtract(i64),
     Multiply(i64),
     Divide(i64),
-    Square
+    Square,
 }
 
 impl Operation {
     pub fn apply(&self, value: i64) -> i64 {
         match self {
-            Operation::Add(op_value) => {
-                value + op_value
-            }
-            Operation::Subtract(op_value) => {
-                value - op_value
-            }
-            Operation::Multiply(op_value) => {
-                value * op_value
-            }
-            Operation::Divide (op_value)=> {
-                value / op_value
-            }
-            Operation::Square => value * value
+            Operation::Add(op_value) => value + op_value,
+            Operation::Subtract(op_value) => value - op_value,
+            Operation::Multiply(op_value) => value * op_value,
+            Operation::Divide(op_value) => value / op_value,
+            Operation::Square => value * value,
         }
     }
 }
 #[derive(Debug)]
-pub enum Test{
-    DivisibleBy(i64)
+pub enum Test {
+    DivisibleBy(i64),
 }
 
 impl Test {
     pub fn check_item(&self, item: &i64) -> bool {
         match self {
-            Test::DivisibleBy(value) => {
-                item % value  == 0
-            }
+            Test::DivisibleBy(value) => item % value == 0,
         }
     }
 }
@@ -83,47 +123,38 @@ pub enum BinOpp {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub enum  Action {
-    ThrowToMonkey(u32)
+pub enum Action {
+    ThrowToMonkey(u32),
 }
 #[derive(Debug)]
 pub struct Monkey {
     pub id: u32,
-    pub number_of_inspected_items : usize,
-    pub starting : Vec<i64>,
+    pub number_of_inspected_items: usize,
+    pub starting: Vec<i64>,
     pub operation: Operation,
     pub test: Test,
     pub if_true: Action,
-    pub if_false: Action
+    pub if_false: Action,
 }
 
 impl Monkey {
-    pub fn do_monkey_things(&mut self) -> Vec<(Action, i64)> {
-        let current_worry_levels : Vec<i64>= self.starting.drain(0..)
-            .into_iter().collect();
+    pub fn do_monkey_things(&mut self, relief: &Relief) -> Vec<(Action, i64)> {
+        let current_worry_levels: Vec<i64> = std::mem::take(&mut self.starting);
         self.number_of_inspected_items += current_worry_levels.len();
-        current_worry_levels.
-            into_iter()
-            .map(|level| self.inspect(level))
+        current_worry_levels
+            .into_iter()
+            .map(|level| self.inspect(level, relief))
             .map(|new_level| (self.decide_next_action(&new_level), new_level))
             .collect()
     }
-    pub fn inspect(&self, item: i64) -> i64 {
-        let new_level = self.operation.apply(item);
-        let because_it_is_boring = new_level / 3;
-        println!(r###"Monkey {}:
-    Monkey inspects an item with a worry level of  {}
-        New worry level is {}.
-        Monkey gets bored with item. Worry level is divided by 3 to {}."###, self.id, item,  &new_level, because_it_is_boring);
-        because_it_is_boring
+    pub fn inspect(&self, item: i64, relief: &Relief) -> i64 {
+        relief.apply(self.operation.apply(item))
     }
 
     pub fn decide_next_action(&self, item: &i64) -> Action {
         if self.test.check_item(item) {
-            println!("\t\tItem is thrown to {:?}",&self.if_true);
             self.if_true.clone()
         } else {
-            println!("\t\tItem is thrown to {:?}",&self.if_false);
             self.if_false.clone()
         }
     }
@@ -136,16 +167,28 @@ pub fn parse_monkeys(input: &str) -> Vec<Monkey> {
         let op = cursoer.next().unwrap();
         match op.as_rule() {
             Rule::add => Operation::Add(cursoer.next().unwrap().as_str().parse().unwrap()),
-            Rule::multiply => Operation::Multiply(cursoer.next().unwrap().as_str().parse().unwrap()),
-            Rule::subtract => Operation::Subtract(cursoer.next().unwrap().as_str().parse().unwrap()),
+            Rule::multiply => {
+                Operation::Multiply(cursoer.next().unwrap().as_str().parse().unwrap())
+            }
+            Rule::subtract => {
+                Operation::Subtract(cursoer.next().unwrap().as_str().parse().unwrap())
+            }
             Rule::divide => Operation::Divide(cursoer.next().unwrap().as_str().parse().unwrap()),
             Rule::square => Operation::Square,
-            _ => panic!("Unknown binary op")
+            _ => panic!("Unknown binary op"),
         }
     }
 
     fn parse_test(test_rule: Pair<Rule>) -> Test {
-        Test::DivisibleBy(test_rule.into_inner().next().unwrap().as_str().parse().unwrap())
+        Test::DivisibleBy(
+            test_rule
+                .into_inner()
+                .next()
+                .unwrap()
+                .as_str()
+                .parse()
+                .unwrap(),
+        )
     }
 
     fn parse_if(test_rule: Pair<Rule>) -> Action {
@@ -157,8 +200,19 @@ pub fn parse_monkeys(input: &str) -> Vec<Monkey> {
     fn parse_monkey(monkey_rules: Pair<Rule>) -> Monkey {
         let mut cursor = monkey_rules.into_inner();
         let id_rule = cursor.next().unwrap();
-        let id = id_rule.into_inner().next().unwrap().as_str().parse().unwrap();
-        let starting: Vec<i64> = cursor.next().unwrap().into_inner().map(|s| s.as_str().parse().unwrap()).collect();
+        let id = id_rule
+            .into_inner()
+            .next()
+            .unwrap()
+            .as_str()
+            .parse()
+            .unwrap();
+        let starting: Vec<i64> = cursor
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|s| s.as_str().parse().unwrap())
+            .collect();
         let operation = parse_operation(cursor.next().unwrap());
         let test = parse_test(cursor.next().unwrap());
         let if_true = parse_if(cursor.next().unwrap());
@@ -170,35 +224,31 @@ pub fn parse_monkeys(input: &str) -> Vec<Monkey> {
             test,
             if_true,
             if_false,
-            number_of_inspected_items: 0
+            number_of_inspected_items: 0,
         }
     }
     fn parse_value(pairs: Pairs<Rule>) -> Vec<Monkey> {
-        pairs.into_iter()
-            .filter_map(|pair| {
-                match pair.as_rule() {
-                    Rule::monkey => {
-                        Some(parse_monkey(pair))
-                    }
-                    Rule::EOI => {
-                        None
-                    }
-                    a => {
-                        println!("I did not expect to find rule {:?} here!", &a);
-                        None
-                    }
+        pairs
+            .filter_map(|pair| match pair.as_rule() {
+                Rule::monkey => Some(parse_monkey(pair)),
+                Rule::EOI => None,
+                a => {
+                    println!("I did not expect to find rule {:?} here!", &a);
+                    None
                 }
             })
             .collect()
     }
     parse_value(MonkeyParser::parse(Rule::monkeys, input).unwrap())
 }
-pub fn do_monkey_dance(mut monkeys: BTreeMap<u32, Monkey>) -> BTreeMap<u32, Monkey> {
-
-    let ids : Vec<u32> = monkeys.keys().cloned().collect();
+pub fn do_monkey_dance(
+    mut monkeys: BTreeMap<u32, Monkey>,
+    relief: &Relief,
+) -> BTreeMap<u32, Monkey> {
+    let ids: Vec<u32> = monkeys.keys().cloned().collect();
     for monkey_id in ids {
         let current_monkey = monkeys.get_mut(&monkey_id).unwrap();
-        let actions = current_monkey.do_monkey_things();
+        let actions = current_monkey.do_monkey_things(relief);
         for (Action::ThrowToMonkey(target), item) in actions {
             let target = monkeys.get_mut(&target).unwrap();
             target.starting.push(item);
@@ -209,12 +259,10 @@ pub fn do_monkey_dance(mut monkeys: BTreeMap<u32, Monkey>) -> BTreeMap<u32, Monk
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-    use pest::iterators::Pair;
-    use pest::Parser;
     use super::*;
+    use std::collections::BTreeMap;
 
-    const TEST_INPUT: &'static str = r###"Monkey 0:
+    const TEST_INPUT: &str = r###"Monkey 0:
   Starting items: 79, 98
   Operation: new = old * 19
   Test: divisible by 23
@@ -245,16 +293,15 @@ Monkey 3:
     #[test]
     pub fn test_parser() {
         let monkeys = parse_monkeys(TEST_INPUT);
-        let mut monkeys : BTreeMap<u32, Monkey> = monkeys.into_iter().map(|m| (m.id, m)).collect();
+        let mut monkeys: BTreeMap<u32, Monkey> = monkeys.into_iter().map(|m| (m.id, m)).collect();
 
         // round one
-        monkeys = do_monkey_dance(monkeys);
+        monkeys = do_monkey_dance(monkeys, &Relief::DivideBy(3));
         let empty: Vec<i64> = vec![];
         assert_eq!(&vec![20, 23, 27, 26], &monkeys[&0].starting);
         assert_eq!(&vec![2080, 25, 167, 207, 401, 1046], &monkeys[&1].starting);
         assert_eq!(&empty, &monkeys[&2].starting);
         assert_eq!(&empty, &monkeys[&3].starting);
-
     }
 
     #[test]
@@ -262,17 +309,64 @@ Monkey 3:
         // Monkey inspects an item with a worry level of  60
         // New worry level is 3600.
         // Monkey gets bored with item. Worry level is divided by 3 to 1200.
-        // Item is thrown to ThrowToMonkey(1)
-        let mut monkey =Monkey {
-            id:2,
+        // Current worry level is not divisible by 13.
+        // Item is thrown to ThrowToMonkey(3)
+        let mut monkey = Monkey {
+            id: 2,
             number_of_inspected_items: 0,
             starting: vec![60],
             operation: Operation::Square,
-            test: Test::DivisibleBy(3),
+            test: Test::DivisibleBy(13),
             if_true: Action::ThrowToMonkey(1),
             if_false: Action::ThrowToMonkey(3),
         };
-        let action = monkey.do_monkey_things();
-        assert_eq!(vec![(Action::ThrowToMonkey(3), 1200 as i64)], action)
+        let action = monkey.do_monkey_things(&Relief::DivideBy(3));
+        assert_eq!(vec![(Action::ThrowToMonkey(3), 1200_i64)], action)
+    }
+
+    #[test]
+    pub fn test_monkey_business() {
+        let business = |rounds, relief| monkey_business(parse_monkeys(TEST_INPUT), rounds, &relief);
+        assert_eq!(10605, business(20, Relief::DivideBy(3)));
+        assert_eq!(96577, common_multiple(&parse_monkeys(TEST_INPUT)));
+        assert_eq!(2713310158, business(10000, Relief::Modulo(96577)));
+    }
+
+    #[test]
+    pub fn test_input() {
+        let input = crate::get_input("../inputs/day11.txt").unwrap();
+        let monkeys = parse_monkeys(&input.join("\n"));
+        assert_eq!(9699690, common_multiple(&monkeys));
+        assert_eq!(
+            "Monkey business after 20 rounds is 100345",
+            part1(input.clone()).unwrap()
+        );
+        assert_eq!(
+            "Monkey business after 10000 rounds is 28537348205",
+            part2(input).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_relief() {
+        // after the first round without relief the monkeys inspected 2, 4, 3 and 6 items
+        let monkeys = parse_monkeys(TEST_INPUT);
+        let monkeys: BTreeMap<u32, Monkey> = monkeys.into_iter().map(|m| (m.id, m)).collect();
+        let monkeys = do_monkey_dance(monkeys, &Relief::None);
+        let inspected: Vec<usize> = monkeys
+            .values()
+            .map(|m| m.number_of_inspected_items)
+            .collect();
+        assert_eq!(vec![2, 4, 3, 6], inspected);
+
+        let custom = Relief::Custom(Box::new(|level| level % 96577));
+        assert_eq!(
+            2713310158,
+            monkey_business(parse_monkeys(TEST_INPUT), 10000, &custom)
+        );
+        assert_eq!(1200, Relief::DivideBy(3).apply(3600));
+        assert_eq!(3600, Relief::None.apply(3600));
+        let relief = Relief::modulo(&parse_monkeys(TEST_INPUT));
+        assert_eq!(3, relief.apply(96580));
     }
 }
@@ -0,0 +1,171 @@
+use crate::day5::{CrateMover, Crates, Move, MoveError};
+
+/// Replays a list of moves and logs the crates every move took, so that moves can be undone
+/// and redone and the stacks can be inspected after any prefix of the list.
+pub struct Rearrangement {
+    crates: Crates,
+    moves: Vec<Move>,
+    mover: CrateMover,
+    // the crates taken by every applied move, bottom to top as they were on the source stack
+    taken: Vec<Vec<char>>,
+}
+
+impl Rearrangement {
+    pub fn new(crates: Crates, moves: Vec<Move>, mover: CrateMover) -> Self {
+        Rearrangement {
+            crates,
+            moves,
+            mover,
+            taken: vec![],
+        }
+    }
+
+    pub fn crates(&self) -> &Crates {
+        &self.crates
+    }
+
+    /// How many moves have been applied.
+    pub fn position(&self) -> usize {
+        self.taken.len()
+    }
+
+    /// Applies the next move, returns `None` once all moves are applied.
+    pub fn redo(&mut self) -> Result<Option<&Move>, MoveError> {
+        let idx = self.position();
+        let Some(muve) = self.moves.get(idx) else {
+            return Ok(None);
+        };
+        let taken = self
+            .crates
+            .stack(muve.from)
+            .map(|source| source[source.len().saturating_sub(muve.count)..].to_vec())
+            .unwrap_or_default();
+        self.crates
+            .move_crates(muve.clone(), self.mover)
+            .map_err(|err| err.at_move(idx))?;
+        self.taken.push(taken);
+        Ok(Some(muve))
+    }
+
+    /// Takes back the last applied move, returns `None` if there is none.
+    pub fn undo(&mut self) -> Option<&Move> {
+        let taken = self.taken.pop()?;
+        let muve = &self.moves[self.taken.len()];
+        if muve.from != muve.to {
+            let target = &mut self.crates.0[muve.to - 1];
+            target.truncate(target.len() - muve.count);
+            self.crates.0[muve.from - 1].extend(taken);
+        }
+        Some(muve)
+    }
+
+    /// Undoes or redoes moves until exactly `position` moves are applied.
+    pub fn seek(&mut self, position: usize) -> Result<(), MoveError> {
+        let position = position.min(self.moves.len());
+        while self.position() > position {
+            self.undo();
+        }
+        while self.position() < position {
+            self.redo()?;
+        }
+        Ok(())
+    }
+
+    /// The stacks after the first `position` moves.
+    pub fn snapshot(&mut self, position: usize) -> Result<Crates, MoveError> {
+        self.seek(position)?;
+        Ok(self.crates.clone())
+    }
+
+    /// The index of the first move after which the given crate is on top of a stack.
+    pub fn first_on_top(&mut self, name: char) -> Result<Option<usize>, MoveError> {
+        self.seek(0)?;
+        while let Some(muve) = self.redo()? {
+            let (from, to) = (muve.from, muve.to);
+            let on_top =
+                |number| self.crates.stack(number).and_then(|stack| stack.last()) == Some(&name);
+            if on_top(from) || on_top(to) {
+                return Ok(Some(self.position() - 1));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day5::parse_move;
+
+    fn example() -> Rearrangement {
+        let crates = Crates(vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]);
+        let moves = [
+            "move 1 from 2 to 1",
+            "move 3 from 1 to 3",
+            "move 2 from 2 to 1",
+            "move 1 from 1 to 2",
+        ]
+        .iter()
+        .map(|line| parse_move(line).unwrap())
+        .collect();
+        Rearrangement::new(crates, moves, CrateMover::Model9000)
+    }
+
+    #[test]
+    pub fn undo_restores_every_step() {
+        let mut rearrangement = example();
+        let start = rearrangement.crates().clone();
+        let mut steps = vec![start.clone()];
+        while rearrangement.redo().unwrap().is_some() {
+            steps.push(rearrangement.crates().clone());
+        }
+        assert_eq!("CMZ", rearrangement.crates().get_top_crates());
+        assert_eq!(None, rearrangement.redo().unwrap());
+
+        while rearrangement.undo().is_some() {
+            assert_eq!(&steps[rearrangement.position()], rearrangement.crates());
+        }
+        assert_eq!(&start, rearrangement.crates());
+    }
+
+    #[test]
+    pub fn snapshots_match_replayed_prefixes() {
+        let mut rearrangement = example();
+        for position in [3, 1, 4, 0, 2] {
+            let mut expected = example().crates().clone();
+            expected
+                .transform(example().moves[..position].to_vec(), CrateMover::Model9000)
+                .unwrap();
+            assert_eq!(expected, rearrangement.snapshot(position).unwrap());
+        }
+    }
+
+    #[test]
+    pub fn finds_first_move_putting_a_crate_on_top() {
+        let mut rearrangement = example();
+        assert_eq!(Some(0), rearrangement.first_on_top('D').unwrap());
+        assert_eq!(Some(1), rearrangement.first_on_top('Z').unwrap());
+        assert_eq!(Some(2), rearrangement.first_on_top('M').unwrap());
+        assert_eq!(None, rearrangement.first_on_top('X').unwrap());
+    }
+
+    #[test]
+    pub fn failing_moves_are_not_logged() {
+        let crates = Crates(vec![vec!['A'], vec![]]);
+        let moves = vec![
+            parse_move("move 1 from 1 to 2").unwrap(),
+            parse_move("move 2 from 2 to 1").unwrap(),
+        ];
+        let mut rearrangement = Rearrangement::new(crates, moves, CrateMover::Model9001);
+        assert_eq!(
+            Err(MoveError::NotEnoughCrates {
+                move_index: 1,
+                stack: 2,
+                requested: 2,
+                available: 1
+            }),
+            rearrangement.seek(2)
+        );
+        assert_eq!(1, rearrangement.position());
+    }
+}
@@ -1,82 +1,235 @@
-use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::io::Write;
 
 use anyhow::{anyhow, Error};
-use iter_tools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub mod history;
+pub mod solver;
 
 pub fn part1(input: Vec<String>) -> Result<String, Error> {
-    let (mut crates, moves) = parse_input(input);
-    crates.transform(moves, true);
+    let (mut crates, moves) = parse_input(input)?;
+    crates.transform(moves, CrateMover::Model9000)?;
     Ok(crates.get_top_crates())
 }
 pub fn part2(input: Vec<String>) -> Result<String, Error> {
-    let (mut crates, moves) = parse_input(input);
-    crates.transform(moves, false);
+    let (mut crates, moves) = parse_input(input)?;
+    crates.transform(moves, CrateMover::Model9001)?;
     Ok(crates.get_top_crates())
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+/// The crane that executes the rearrangement.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum CrateMover {
+    /// Lifts one crate at a time, so moved crates end up in reverse order.
+    Model9000,
+    /// Lifts all crates of a move at once, so they keep their order.
+    Model9001,
+    /// Lifts at most the given number of crates at once. Each grab keeps its order.
+    Limited(usize),
+}
+
+impl CrateMover {
+    /// How many crates the crane lifts in one grab when asked to move `count` crates.
+    pub fn grab_size(&self, count: usize) -> usize {
+        match self {
+            CrateMover::Model9000 => 1,
+            CrateMover::Model9001 => count.max(1),
+            CrateMover::Limited(max) => (*max).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Move {
     pub count: usize,
     pub from: usize,
     pub to: usize,
 }
 
-pub struct Crates(HashMap<usize, Vec<char>>);
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+/// Why a move could not be executed. `move_index` is the position of the move in the
+/// list given to `transform`, a single `move_crates` reports it as 0.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum MoveError {
+    UnknownStack {
+        move_index: usize,
+        stack: usize,
+    },
+    NotEnoughCrates {
+        move_index: usize,
+        stack: usize,
+        requested: usize,
+        available: usize,
+    },
+}
+
+impl MoveError {
+    fn at_move(self, index: usize) -> Self {
+        match self {
+            MoveError::UnknownStack { stack, .. } => MoveError::UnknownStack {
+                move_index: index,
+                stack,
+            },
+            MoveError::NotEnoughCrates {
+                stack,
+                requested,
+                available,
+                ..
+            } => MoveError::NotEnoughCrates {
+                move_index: index,
+                stack,
+                requested,
+                available,
+            },
+        }
+    }
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::UnknownStack { move_index, stack } => {
+                write!(f, "Move {} uses unknown stack {}", move_index, stack)
+            }
+            MoveError::NotEnoughCrates {
+                move_index,
+                stack,
+                requested,
+                available,
+            } => write!(
+                f,
+                "Move {} takes {} crates from stack {}, but only {} are available",
+                move_index, requested, stack, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// The stacks in the order of their numbers, stack `n` lives at index `n - 1`.
+/// The top crate of every stack is its last element.
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
+pub struct Crates(Vec<Vec<char>>);
 
+// draws the stacks the way the puzzle does, so the output can be read by `parse_stacks` again
 impl Display for Crates {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let str = self
-            .0
-            .iter()
-            .map(|(row, creates)| {
-                format!(
-                    "{} : {}",
-                    row,
-                    creates
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-        write!(f, "{}", str)
+        let height = self.0.iter().map(|stack| stack.len()).max().unwrap_or(0);
+        let mut lines = vec![];
+        for level in (0..height).rev() {
+            let line = self
+                .0
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(name) => format!("[{}]", name),
+                    None => "   ".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            lines.push(line.trim_end().to_string());
+        }
+        lines.push(
+            (1..=self.0.len())
+                .map(|column| format!(" {} ", column))
+                .collect::<Vec<String>>()
+                .join(" ")
+                .trim_end()
+                .to_string(),
+        );
+        write!(f, "{}", lines.join("\n"))
     }
 }
 impl Crates {
-    pub fn move_crates(&mut self, crates_move: Move, keep_order: bool) {
-        let [source, taget] = self
-            .0
-            .get_many_mut([&crates_move.from, &crates_move.to])
-            .expect("Invalid move!");
-        let removed = source.drain(0..crates_move.count);
-        if keep_order {
-            removed.for_each(|c| taget.insert(0, c));
+    fn stack(&self, number: usize) -> Option<&Vec<char>> {
+        number.checked_sub(1).and_then(|idx| self.0.get(idx))
+    }
+
+    pub fn check_move(&self, crates_move: &Move) -> Result<(), MoveError> {
+        if self.stack(crates_move.to).is_none() {
+            return Err(MoveError::UnknownStack {
+                move_index: 0,
+                stack: crates_move.to,
+            });
+        }
+        match self.stack(crates_move.from) {
+            None => Err(MoveError::UnknownStack {
+                move_index: 0,
+                stack: crates_move.from,
+            }),
+            Some(source) if source.len() < crates_move.count => Err(MoveError::NotEnoughCrates {
+                move_index: 0,
+                stack: crates_move.from,
+                requested: crates_move.count,
+                available: source.len(),
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn move_crates(&mut self, crates_move: Move, mover: CrateMover) -> Result<(), MoveError> {
+        self.check_move(&crates_move)?;
+        let (from, to) = (crates_move.from - 1, crates_move.to - 1);
+        if from == to {
+            // every grab is put back where it came from
+            return Ok(());
+        }
+        let (source, target) = if from < to {
+            let (lower, upper) = self.0.split_at_mut(to);
+            (&mut lower[from], &mut upper[0])
         } else {
-            removed.rev().for_each(|c| taget.insert(0, c));
+            let (lower, upper) = self.0.split_at_mut(from);
+            (&mut upper[0], &mut lower[to])
+        };
+        let remaining = source.len() - crates_move.count;
+        // the first grab takes the topmost crates and ends up at the bottom of the target
+        let grab_size = mover.grab_size(crates_move.count);
+        target.extend(source[remaining..].rchunks(grab_size).flatten());
+        source.truncate(remaining);
+        Ok(())
+    }
+    pub fn transform(&mut self, moves: Vec<Move>, mover: CrateMover) -> Result<(), MoveError> {
+        for (idx, muve) in moves.into_iter().enumerate() {
+            self.move_crates(muve, mover)
+                .map_err(|err| err.at_move(idx))?;
         }
+        Ok(())
     }
-    pub fn transform(&mut self, moves: Vec<Move>, keep_order: bool) {
-        for muve in moves {
-            self.move_crates(muve, keep_order);
+
+    // like `transform`, but draws the stacks before the first and after every move
+    pub fn trace<W: Write>(
+        &mut self,
+        moves: Vec<Move>,
+        mover: CrateMover,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        writeln!(out, "{}\n", self)?;
+        for (idx, muve) in moves.into_iter().enumerate() {
+            writeln!(out, "{}", muve)?;
+            self.move_crates(muve, mover)
+                .map_err(|err| err.at_move(idx))?;
+            writeln!(out, "{}\n", self)?;
         }
+        Ok(())
     }
 
+    // empty stacks show up as a blank
     pub fn get_top_crates(&self) -> String {
-        let mut cols: Vec<usize> = self.0.keys().cloned().collect();
-        cols.sort();
-        let mut res = String::new();
-        for colum in cols {
-            res.push(self.0[&colum].first().unwrap().clone())
-        }
-        res
+        self.0
+            .iter()
+            .map(|stack| *stack.last().unwrap_or(&' '))
+            .collect()
     }
 }
 
 pub fn parse_move(line: &str) -> Result<Move, Error> {
-    let parts = line.split(" ").into_iter().collect::<Vec<&str>>();
+    let parts = line.split(" ").collect::<Vec<&str>>();
     match parts[..] {
         [_, count, _, from, _, to] => Ok(Move {
             count: count.parse()?,
@@ -90,7 +243,6 @@ pub fn parse_move(line: &str) -> Result<Move, Error> {
 pub fn parse_stack(line: &str) -> Vec<(usize, char)> {
     let line: Vec<(usize, char)> = line
         .chars()
-        .into_iter()
         .enumerate()
         .filter_map(|(idx, char)| {
             if char.is_alphabetic() {
@@ -103,34 +255,96 @@ pub fn parse_stack(line: &str) -> Vec<(usize, char)> {
     line
 }
 
-pub fn parse_stacks<'a, A: Iterator<Item = &'a String>>(input: A) -> HashMap<usize, Vec<char>> {
-    input.fold(HashMap::new(), |mut crates, line| {
-        for (idx, name) in parse_stack(&line) {
-            let stack = crates.entry(idx).or_insert(Vec::new());
-            stack.push(name);
+// the row with the stack numbers is used to create stacks that start out empty
+pub fn parse_stacks<'a, A: Iterator<Item = &'a String>>(input: A) -> Vec<Vec<char>> {
+    let mut stacks = input.fold(Vec::new(), |mut crates: Vec<Vec<char>>, line| {
+        let numbers = line
+            .split_whitespace()
+            .map(|number| number.parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .unwrap_or_default();
+        for number in numbers {
+            if crates.len() < number {
+                crates.resize(number, Vec::new());
+            }
+        }
+        for (idx, name) in parse_stack(line) {
+            if crates.len() < idx {
+                crates.resize(idx, Vec::new());
+            }
+            crates[idx - 1].push(name);
         }
         crates
-    })
+    });
+    // we read the stacks from top to bottom
+    stacks.iter_mut().for_each(|stack| stack.reverse());
+    stacks
 }
 
-pub fn parse_moves<'a, A: Iterator<Item = &'a String>>(input: A) -> Vec<Move> {
+pub fn parse_moves<'a, A: Iterator<Item = &'a String>>(input: A) -> Result<Vec<Move>, Error> {
     input
-        .map(|line| parse_move(&line).unwrap_or_else(|e| panic!("Failed to parse move {}", e)))
+        .map(|line| parse_move(line).map_err(|e| anyhow!("Failed to parse move {}: {}", line, e)))
         .collect()
 }
 
-fn parse_input(input: Vec<String>) -> (Crates, Vec<Move>) {
+// the stacks and the moves are separated by an empty line
+fn parse_input(input: Vec<String>) -> Result<(Crates, Vec<Move>), Error> {
     let mut lines = input.split(|line| line.is_empty());
-    let stacks = parse_stacks(lines.next().unwrap().iter());
-    let moves = parse_moves(lines.next().unwrap().iter());
-    (Crates(stacks), moves)
+    let stacks = parse_stacks(lines.next().unwrap_or_default().iter());
+    let moves = lines
+        .next()
+        .ok_or_else(|| anyhow!("No empty line before the moves"))?;
+    Ok((Crates(stacks), parse_moves(moves.iter())?))
+}
+
+/// The starting stacks together with the moves, to exchange them with other tools.
+/// In JSON every stack is a list of crates from the bottom to the top.
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Procedure {
+    pub crates: Crates,
+    pub moves: Vec<Move>,
+}
+
+impl Procedure {
+    pub fn from_text(input: Vec<String>) -> Result<Self, Error> {
+        let (crates, moves) = parse_input(input)?;
+        Ok(Procedure { crates, moves })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n\n", self.crates);
+        for muve in &self.moves {
+            text.push_str(&format!("{}\n", muve));
+        }
+        text
+    }
+
+    /// Crates have to be letters, like in the text the puzzle uses.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let procedure: Procedure =
+            serde_json::from_str(json).map_err(|e| anyhow!("Invalid crates procedure: {}", e))?;
+        match procedure
+            .crates
+            .0
+            .iter()
+            .flatten()
+            .find(|c| !c.is_alphabetic())
+        {
+            Some(name) => Err(anyhow!("Invalid crate '{}', crates are letters", name)),
+            None => Ok(procedure),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const TEST_INPUT: &'static str = r###"    [D]
+    const TEST_INPUT: &str = r###"    [D]
 [N] [C]
 [Z] [M] [P]
  1   2   3
@@ -141,11 +355,18 @@ move 2 from 2 to 1
 move 1 from 1 to 2
 "###;
 
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(|l| l.to_string()).collect()
+    }
+
+    fn example() -> (Crates, Vec<Move>) {
+        super::parse_input(lines(TEST_INPUT)).unwrap()
+    }
+
     #[test]
     pub fn test_parser() {
         let results = TEST_INPUT
             .split("\n")
-            .into_iter()
             .map(parse_stack)
             .filter(|l| !l.is_empty())
             .collect::<Vec<Vec<(usize, char)>>>();
@@ -165,17 +386,185 @@ move 1 from 1 to 2
 
     #[test]
     pub fn test_example() {
-        let (mut crates, moves) = super::parse_input(
-            TEST_INPUT
-                .split("\n")
-                .into_iter()
-                .map(|l| l.to_string())
-                .collect(),
-        );
+        let (mut crates, moves) = example();
         println!("{}", crates);
         println!("{}", crates.get_top_crates());
-        crates.transform(moves, true);
+        crates.transform(moves, CrateMover::Model9000).unwrap();
         println!("{}", crates);
         println!("{}", crates.get_top_crates());
     }
+
+    fn top_crates_with(mover: CrateMover) -> String {
+        let (mut crates, moves) = example();
+        crates.transform(moves, mover).unwrap();
+        crates.get_top_crates()
+    }
+
+    #[test]
+    pub fn test_crate_movers() {
+        assert_eq!("CMZ", top_crates_with(CrateMover::Model9000));
+        assert_eq!("MCD", top_crates_with(CrateMover::Model9001));
+        assert_eq!("CMZ", top_crates_with(CrateMover::Limited(1)));
+        assert_eq!("MCD", top_crates_with(CrateMover::Limited(3)));
+    }
+
+    #[test]
+    pub fn test_limited_crate_mover() {
+        let mut crates = Crates(vec![vec!['E', 'D', 'C', 'B', 'A'], vec![]]);
+        crates
+            .move_crates(
+                Move {
+                    count: 5,
+                    from: 1,
+                    to: 2,
+                },
+                CrateMover::Limited(2),
+            )
+            .unwrap();
+        assert_eq!(vec!['B', 'A', 'D', 'C', 'E'], crates.0[1]);
+    }
+
+    #[test]
+    pub fn test_checked_moves() {
+        let (mut crates, _) = example();
+        let moves = vec![
+            parse_move("move 1 from 2 to 1").unwrap(),
+            parse_move("move 3 from 2 to 3").unwrap(),
+        ];
+        assert_eq!(
+            Err(MoveError::NotEnoughCrates {
+                move_index: 1,
+                stack: 2,
+                requested: 3,
+                available: 2
+            }),
+            crates.transform(moves, CrateMover::Model9000)
+        );
+        assert_eq!(
+            Err(MoveError::UnknownStack {
+                move_index: 0,
+                stack: 7
+            }),
+            crates.move_crates(
+                parse_move("move 1 from 1 to 7").unwrap(),
+                CrateMover::Model9000
+            )
+        );
+    }
+
+    #[test]
+    pub fn test_top_crates_of_empty_stacks() {
+        let mut crates = Crates(vec![vec!['A'], vec![], vec!['B']]);
+        assert_eq!("A B", crates.get_top_crates());
+        crates
+            .move_crates(
+                parse_move("move 1 from 1 to 3").unwrap(),
+                CrateMover::Model9000,
+            )
+            .unwrap();
+        assert_eq!("  A", crates.get_top_crates());
+    }
+
+    #[test]
+    pub fn test_drawing_round_trips() {
+        let (crates, _) = example();
+        let drawing = crates.to_string();
+        assert_eq!(
+            TEST_INPUT
+                .split("\n")
+                .take(4)
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            drawing
+        );
+
+        assert_eq!(crates, Crates(parse_stacks(lines(&drawing).iter())));
+    }
+
+    #[test]
+    pub fn test_empty_stacks_round_trip() {
+        let crates = Crates(vec![vec![], vec!['A'], vec![]]);
+        let drawing = crates.to_string();
+        assert_eq!("    [A]\n 1   2   3", drawing);
+
+        assert_eq!(crates, Crates(parse_stacks(lines(&drawing).iter())));
+    }
+
+    #[test]
+    pub fn test_trace() {
+        let (mut crates, moves) = example();
+        let mut out = vec![];
+        crates
+            .trace(moves[..1].to_vec(), CrateMover::Model9000, &mut out)
+            .unwrap();
+        assert_eq!(
+            r###"    [D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
+
+move 1 from 2 to 1
+[D]
+[N] [C]
+[Z] [M] [P]
+ 1   2   3
+
+"###,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_moves_between_many_stacks() {
+        let stacks = 5000;
+        let mut crates = Crates(
+            (0..stacks)
+                .map(|idx| vec![char::from(b'A' + (idx % 26) as u8); 3])
+                .collect(),
+        );
+        let moves: Vec<Move> = (0..2_000_000)
+            .map(|idx| Move {
+                count: 3,
+                from: idx % stacks + 1,
+                to: (idx + 1) % stacks + 1,
+            })
+            .collect();
+        crates.transform(moves, CrateMover::Model9001).unwrap();
+        assert_eq!(
+            15000,
+            crates.0.iter().map(|stack| stack.len()).sum::<usize>()
+        );
+    }
+
+    #[test]
+    pub fn test_procedure_as_json() {
+        let procedure = Procedure::from_text(lines(TEST_INPUT)).unwrap();
+        let json = procedure.to_json().unwrap();
+        assert_eq!(procedure, Procedure::from_json(&json).unwrap());
+
+        let procedure = Procedure::from_json(
+            r#"{
+                "crates": [["Z", "N"], ["M", "C", "D"], ["P"]],
+                "moves": [{"count": 1, "from": 2, "to": 1}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!("NDP", procedure.crates.get_top_crates());
+        assert_eq!(
+            vec![parse_move("move 1 from 2 to 1").unwrap()],
+            procedure.moves
+        );
+        assert!(Procedure::from_json(r#"{"crates": [["ZN"]], "moves": []}"#).is_err());
+        assert!(Procedure::from_json(r#"{"crates": [["1"]], "moves": []}"#).is_err());
+        assert!(Procedure::from_json(r#"{"crates": [["Z", " "]], "moves": []}"#).is_err());
+    }
+
+    #[test]
+    pub fn test_procedure_as_text() {
+        let procedure = Procedure::from_text(lines(TEST_INPUT)).unwrap();
+        assert_eq!(TEST_INPUT, procedure.to_text());
+
+        assert!(Procedure::from_text(lines("[A]\n 1\n\nmove one from 1 to 1")).is_err());
+        assert!(Procedure::from_text(lines("[A]\n 1")).is_err());
+    }
 }
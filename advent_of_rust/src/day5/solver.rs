@@ -0,0 +1,140 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::day5::{CrateMover, Crates, Move};
+
+/// What the rearrangement should end up with.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Target {
+    /// Every stack has to look exactly like this.
+    Layout(Crates),
+    /// Only the top crates matter, read like `Crates::get_top_crates`.
+    TopCrates(String),
+}
+
+impl Target {
+    pub fn is_reached(&self, crates: &Crates) -> bool {
+        match self {
+            Target::Layout(layout) => layout == crates,
+            Target::TopCrates(tops) => &crates.get_top_crates() == tops,
+        }
+    }
+}
+
+fn same_crates(a: &Crates, b: &Crates) -> bool {
+    let crates_of = |crates: &Crates| {
+        let mut all: Vec<char> = crates.0.iter().flatten().cloned().collect();
+        all.sort();
+        (crates.0.len(), all)
+    };
+    crates_of(a) == crates_of(b)
+}
+
+fn possible_moves(crates: &Crates) -> Vec<Move> {
+    let mut moves = vec![];
+    for (from, stack) in crates.0.iter().enumerate() {
+        for to in (0..crates.0.len()).filter(|to| *to != from) {
+            for count in 1..=stack.len() {
+                moves.push(Move {
+                    count,
+                    from: from + 1,
+                    to: to + 1,
+                });
+            }
+        }
+    }
+    moves
+}
+
+/// Searches breadth first for a shortest list of moves that turns `start` into the `target`
+/// using the given crane. Gives up after `max_moves` moves, the search space grows quickly.
+pub fn find_moves(
+    start: &Crates,
+    target: &Target,
+    mover: CrateMover,
+    max_moves: usize,
+) -> Option<Vec<Move>> {
+    if let Target::Layout(layout) = target {
+        if !same_crates(start, layout) {
+            return None;
+        }
+    }
+    // every visited layout with the index of its predecessor and the move leading to it
+    let mut visited: Vec<(Crates, Option<(usize, Move)>)> = vec![(start.clone(), None)];
+    let mut seen = HashSet::from([start.clone()]);
+    let mut queue = VecDeque::from([(0, 0)]);
+    while let Some((idx, depth)) = queue.pop_front() {
+        if target.is_reached(&visited[idx].0) {
+            let mut moves = vec![];
+            let mut current = idx;
+            while let Some((previous, muve)) = visited[current].1.clone() {
+                moves.push(muve);
+                current = previous;
+            }
+            moves.reverse();
+            return Some(moves);
+        }
+        if depth == max_moves {
+            continue;
+        }
+        for muve in possible_moves(&visited[idx].0) {
+            let mut next = visited[idx].0.clone();
+            next.move_crates(muve.clone(), mover)
+                .expect("only valid moves are generated");
+            if seen.insert(next.clone()) {
+                visited.push((next, Some((idx, muve))));
+                queue.push_back((visited.len() - 1, depth + 1));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day5::parse_move;
+
+    fn apply(start: &Crates, moves: &[Move], mover: CrateMover) -> Crates {
+        let mut crates = start.clone();
+        crates.transform(moves.to_vec(), mover).unwrap();
+        crates
+    }
+
+    #[test]
+    pub fn finds_shortest_moves_for_layout() {
+        let start = Crates(vec![vec!['B', 'A'], vec![], vec![]]);
+        let target = Target::Layout(Crates(vec![vec![], vec!['B', 'A'], vec![]]));
+
+        let moves = find_moves(&start, &target, CrateMover::Model9001, 5).unwrap();
+        assert_eq!(vec![parse_move("move 2 from 1 to 2").unwrap()], moves);
+
+        let moves = find_moves(&start, &target, CrateMover::Model9000, 5).unwrap();
+        assert_eq!(2, moves.len());
+        assert!(target.is_reached(&apply(&start, &moves, CrateMover::Model9000)));
+    }
+
+    #[test]
+    pub fn finds_moves_for_top_crates() {
+        let start = Crates(vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]);
+        let target = Target::TopCrates("CMZ".to_string());
+        let moves = find_moves(&start, &target, CrateMover::Model9000, 4).unwrap();
+        assert!(moves.len() <= 4);
+        assert_eq!(
+            "CMZ",
+            apply(&start, &moves, CrateMover::Model9000).get_top_crates()
+        );
+    }
+
+    #[test]
+    pub fn gives_up_on_unreachable_targets() {
+        let start = Crates(vec![vec!['A'], vec![]]);
+        let other_crates = Target::Layout(Crates(vec![vec![], vec!['B']]));
+        assert_eq!(
+            None,
+            find_moves(&start, &other_crates, CrateMover::Model9000, 10)
+        );
+
+        let too_far = Target::TopCrates("XA".to_string());
+        assert_eq!(None, find_moves(&start, &too_far, CrateMover::Model9000, 3));
+    }
+}
@@ -1,7 +1,10 @@
 extern crate core;
 extern crate pest;
-#[macro_use]
-extern crate pest_derive;
+
+pub mod day06;
+pub mod day07;
+pub mod day11;
+pub mod day5;
 
 use std::env;
 use std::io::{self, BufRead};
@@ -12,19 +15,22 @@ use anyhow::anyhow;
 use anyhow::Error;
 
 pub fn get_file_path() -> String {
-    let args: Vec<String> = std::env::args().into_iter().collect();
+    let args: Vec<String> = std::env::args().collect();
     args.get(1)
         .cloned()
         .unwrap_or_else(|| panic!("Give me an input file please"))
 }
 
 pub fn get_input<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
-    let curPath = env::current_dir()?;
-    println!("The current directory is {}", curPath.display());
+    let cur_path = env::current_dir()?;
+    println!("The current directory is {}", cur_path.display());
     let file = std::fs::File::open(&path)
         .map_err(|e| anyhow!("Failed to open file '{:?}'. {}", path.as_ref(), e))?;
+    // lines that are not UTF-8 are skipped, other read errors are returned since they can repeat forever
     let lines = io::BufReader::new(file).lines();
-    Ok(lines.filter_map(|line| line.ok()).collect::<Vec<String>>())
+    Ok(lines
+        .filter(|line| !matches!(line, Err(e) if e.kind() == io::ErrorKind::InvalidData))
+        .collect::<Result<Vec<String>, _>>()?)
 }
 
 pub fn run_solution<A, B>(file_path: &'static str, part_one: A, part_tow: B) -> Result<(), Error>